@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use crate::error::{AsmError, AsmErrorKind, EncodeError, ParseError};
+use crate::instruction::Instruction;
+use crate::register::Register;
+use crate::parser::parse_imm;
+
+/// Pseudo-instructions that expand to more than one instruction, and their
+/// number of operands. The single-instruction ones are handled by `FromStr`.
+const PSEUDO_OPERANDS: [(&str, usize); 4] = [("li", 2), ("la", 2), ("call", 1), ("tail", 1)];
+
+/// Mnemonics whose last operand is a branch or jump target, which can be a label.
+const TARGET_MNEMONICS: [&str; 18] = [
+    "beq", "bne", "blt", "bge", "bltu", "bgeu",
+    "beqz", "bnez", "blez", "bgez", "bltz", "bgtz",
+    "bgt", "ble", "bgtu", "bleu", "j", "jal",
+];
+
+/// Most zero bytes one `.org` may add, so a typo can't allocate the address space.
+const MAX_FILL: u64 = 1 << 24;
+
+enum Body<'a> {
+    Instruction { mnemonic: String, operands: Vec<&'a str> },
+    Data { width: usize, values: Vec<&'a str> },
+    /// Zero bytes from `.align` and `.org`.
+    Fill(u64),
+}
+
+struct Statement<'a> {
+    line: usize,
+    address: u64,
+    body: Body<'a>,
+}
+
+/// Register names can't be labels, `a0:` would make `mv a1, a0` ambiguous.
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$'))
+        && s.parse::<Register>().is_err()
+}
+
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find('#'), line.find("//")].into_iter().flatten().min();
+    &line[..end.unwrap_or(line.len())]
+}
+
+/// Splits `offset` into the `auipc` and 12-bit parts of a PC-relative address.
+fn split_pcrel(offset: i64) -> (i64, i64) {
+    let hi = offset.wrapping_add(0x800) >> 12;
+    (hi, offset.wrapping_sub(hi << 12))
+}
+
+/// Materializes a 64-bit constant the way LLVM does: `lui`/`addiw` for the low
+/// 32 bits, then the upper bits are built recursively and shifted into place.
+fn load_imm(rd: &str, imm: i64, out: &mut Vec<String>) {
+    let lo12 = imm << 52 >> 52;
+    if imm == imm as i32 as i64 {
+        let hi20 = (imm.wrapping_add(0x800) >> 12) & 0xFFFFF;
+        if hi20 != 0 {
+            out.push(format!("lui {}, {}", rd, hi20));
+            if lo12 != 0 {
+                out.push(format!("addiw {}, {}, {}", rd, rd, lo12));
+            }
+        } else {
+            out.push(format!("addi {}, zero, {}", rd, lo12));
+        }
+        return;
+    }
+    let hi52 = (imm.wrapping_add(0x800) as u64 >> 12) as i64;
+    let shift = 12 + hi52.trailing_zeros();
+    let hi = (hi52 >> (shift - 12)) << shift >> shift;
+    load_imm(rd, hi, out);
+    out.push(format!("slli {}, {}, {}", rd, rd, shift));
+    if lo12 != 0 {
+        out.push(format!("addi {}, {}, {}", rd, rd, lo12));
+    }
+}
+
+/// Expands a source instruction at `pc` into real instructions, `li`, `la`,
+/// `call` and `tail` become several base instructions and label targets become offsets.
+fn expand(mnemonic: &str, ops: &[&str], pc: u64, labels: &dyn Fn(&str) -> Option<u64>) -> Result<Vec<Instruction>, AsmErrorKind> {
+    if let Some((_, expected)) = PSEUDO_OPERANDS.iter().find(|(name, _)| *name == mnemonic) {
+        if ops.len() != *expected {
+            return Err(ParseError::OperandCount { expected: *expected, found: ops.len() }.into());
+        }
+    }
+    let offset = |target: &str| -> Result<i64, AsmErrorKind> {
+        if is_label(target) {
+            labels(target)
+                .map(|address| address.wrapping_sub(pc) as i64)
+                .ok_or_else(|| AsmErrorKind::UndefinedLabel(target.to_string()))
+        } else {
+            Ok(parse_imm(target)?)
+        }
+    };
+
+    let lines = match (mnemonic, ops) {
+        ("li", [rd, imm]) => {
+            let mut lines = Vec::new();
+            load_imm(rd, parse_imm(imm)?, &mut lines);
+            lines
+        }
+        ("la", [rd, target]) => {
+            let (hi, lo) = split_pcrel(offset(target)?);
+            vec![format!("auipc {}, {}", rd, hi), format!("addi {}, {}, {}", rd, rd, lo)]
+        }
+        ("call", [target]) => {
+            let (hi, lo) = split_pcrel(offset(target)?);
+            vec![format!("auipc ra, {}", hi), format!("jalr ra, {}(ra)", lo)]
+        }
+        ("tail", [target]) => {
+            let (hi, lo) = split_pcrel(offset(target)?);
+            vec![format!("auipc t1, {}", hi), format!("jalr zero, {}(t1)", lo)]
+        }
+        (_, [rest @ .., target]) if TARGET_MNEMONICS.contains(&mnemonic) => {
+            let offset = offset(target)?.to_string();
+            let mut ops = rest.to_vec();
+            ops.push(&offset);
+            vec![format!("{} {}", mnemonic, ops.join(", "))]
+        }
+        _ => vec![format!("{} {}", mnemonic, ops.join(", "))],
+    };
+    lines.iter().map(|line| Ok(line.parse::<Instruction>()?)).collect()
+}
+
+fn encoded_len(inst: &Instruction) -> usize {
+    match inst {
+        Instruction::Long { len, .. } => *len,
+        _ => 4,
+    }
+}
+
+/// Checks that `value` fits in `width` bytes as either a signed or an unsigned number.
+fn check_data(value: i64, width: usize) -> Result<i64, ParseError> {
+    if width == 8 {
+        return Ok(value);
+    }
+    let (min, max) = (-(1 << (width * 8 - 1)), (1 << (width * 8)) - 1);
+    if value < min || value > max {
+        return Err(ParseError::Encode(EncodeError::ImmOutOfRange { imm: value, min, max }));
+    }
+    Ok(value)
+}
+
+/// Assembles RV64 source into a flat little-endian binary loaded at `origin`.
+///
+/// Lines hold an optional `label:`, then an instruction in the syntax
+/// [`Instruction`]'s `FromStr` accepts, a pseudo-instruction (`li`, `la`, `call`,
+/// `tail`, `mv`, `j`, `beqz`, ...) or a directive (`.byte`, `.half`, `.word`,
+/// `.dword`, `.align n` for 2^n bytes, `.org address`). Comments start with `#`
+/// or `//`. Branch and jump targets are labels or numeric offsets from the
+/// instruction, and nothing is compressed.
+pub fn assemble(source: &str, origin: u64) -> Result<Vec<u8>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = origin;
+
+    for (index, line) in source.lines().enumerate() {
+        let err = |kind| AsmError { line: index + 1, kind };
+        let mut text = strip_comment(line).trim();
+        while let Some((label, rest)) = text.split_once(':').filter(|(label, _)| is_label(label.trim())) {
+            if labels.insert(label.trim().to_string(), address).is_some() {
+                return Err(err(AsmErrorKind::DuplicateLabel(label.trim().to_string())));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let mnemonic = mnemonic.to_lowercase();
+        let operands: Vec<&str> = if rest.trim().is_empty() { Vec::new() } else { rest.split(',').map(str::trim).collect() };
+        let single = || match operands[..] {
+            [op] => parse_imm(op).map_err(|e| err(e.into())),
+            _ => Err(err(ParseError::OperandCount { expected: 1, found: operands.len() }.into())),
+        };
+
+        let body = match mnemonic.as_str() {
+            ".byte" => Body::Data { width: 1, values: operands },
+            ".half" => Body::Data { width: 2, values: operands },
+            ".word" => Body::Data { width: 4, values: operands },
+            ".dword" => Body::Data { width: 8, values: operands },
+            ".align" => match single()? {
+                n @ 0..=16 => match address.checked_next_multiple_of(1 << n) {
+                    Some(next) => Body::Fill(next - address),
+                    None => return Err(err(AsmErrorKind::AddressOverflow)),
+                },
+                n => return Err(err(ParseError::InvalidImm(n.to_string()).into())),
+            },
+            ".org" => match single()? as u64 {
+                // Hex addresses above i64::MAX are fine, negative ones aren't addresses
+                _ if operands[0].starts_with('-') => return Err(err(ParseError::InvalidImm(operands[0].to_string()).into())),
+                org if org < address => return Err(err(AsmErrorKind::OrgBackwards { org, address })),
+                org if org - address > MAX_FILL => return Err(err(AsmErrorKind::FillTooLarge { len: org - address })),
+                org => Body::Fill(org - address),
+            },
+            ".text" | ".globl" | ".global" => continue,
+            name if name.starts_with('.') && name != ".insn" => return Err(err(AsmErrorKind::UnknownDirective(mnemonic))),
+            _ => Body::Instruction { mnemonic, operands },
+        };
+        // Labels aren't known yet, but only their values depend on them, not the size
+        let size = match &body {
+            Body::Instruction { mnemonic, operands } => {
+                let insts = expand(mnemonic, operands, address, &|_| Some(address)).map_err(err)?;
+                insts.iter().map(encoded_len).sum::<usize>() as u64
+            }
+            Body::Data { width, values } => (width * values.len()) as u64,
+            Body::Fill(len) => *len,
+        };
+        statements.push(Statement { line: index + 1, address, body });
+        address = address.checked_add(size).ok_or_else(|| err(AsmErrorKind::AddressOverflow))?;
+    }
+
+    let mut out = Vec::new();
+    for statement in statements {
+        let err = |kind| AsmError { line: statement.line, kind };
+        match statement.body {
+            Body::Instruction { mnemonic, operands } => {
+                for inst in expand(&mnemonic, &operands, statement.address, &|label| labels.get(label).copied()).map_err(err)? {
+                    match inst {
+                        Instruction::Long { len, raw } => out.extend_from_slice(&raw[..len]),
+                        _ => out.extend_from_slice(&inst.encode().map_err(|e| err(ParseError::from(e).into()))?.to_le_bytes()),
+                    }
+                }
+            }
+            Body::Data { width, values } => {
+                for value in values {
+                    let value = match labels.get(value) {
+                        Some(address) => check_data(*address as i64, width).map_err(|e| err(e.into()))?,
+                        None if is_label(value) => return Err(err(AsmErrorKind::UndefinedLabel(value.to_string()))),
+                        None => check_data(parse_imm(value).map_err(|e| err(e.into()))?, width).map_err(|e| err(e.into()))?,
+                    };
+                    out.extend_from_slice(&value.to_le_bytes()[..width]);
+                }
+            }
+            Body::Fill(len) => out.resize(out.len() + len as usize, 0),
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,149 @@
+//! Decodes instruction words or bytes given as hex, from the arguments or one
+//! input per line on stdin.
+//!
+//! Tokens with a `0x` prefix or more than two digits are words in the order
+//! objdump prints them (`0x00050433`, `4501`), two-digit tokens are bytes in
+//! memory order (`13 05 a0 00`). Everything on one line is decoded as a single
+//! stream, so several instructions can be given at once. `--json` prints one
+//! JSON object per instruction instead, and `rv64dec explain ...` shows the
+//! bit fields of each word as typed, and which one made decoding fail.
+
+use std::io::{BufRead, IsTerminal};
+use std::process::ExitCode;
+use rv64_decoder::{opcode_size, Ansi, Decoder, FormatOptions, InstructionFormat, InstructionStream, Plain, Syntax};
+
+const USAGE: &str = "usage: rv64dec [explain] [--objdump] [--no-aliases] [--json] [--isa <string>] [hex...]";
+
+/// Turns one line of hex tokens into little-endian bytes, one run per token.
+/// Byte tokens give a single byte.
+fn parse_line(line: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut tokens = Vec::new();
+    for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|token| !token.is_empty()) {
+        let digits = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X"));
+        let invalid = || format!("invalid hex `{}`", token);
+        match digits {
+            None if token.len() == 2 => tokens.push(vec![u8::from_str_radix(token, 16).map_err(|_| invalid())?]),
+            _ => {
+                let digits = digits.unwrap_or(token).replace('_', "");
+                let value = u64::from_str_radix(&digits, 16).map_err(|_| invalid())?;
+                // Leading zeros count, `00000013` is a 32-bit word
+                let len = opcode_size(value as u32).max(digits.len().div_ceil(4) * 2).min(8);
+                tokens.push(value.to_le_bytes()[..len].to_vec());
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// The first 32 bits of `bytes`, zero-extended.
+fn first_word(bytes: &[u8]) -> u32 {
+    let mut raw = [0u8; 4];
+    raw[..bytes.len().min(4)].copy_from_slice(&bytes[..bytes.len().min(4)]);
+    u32::from_le_bytes(raw)
+}
+
+/// Explains each word token as typed, runs of byte tokens are split into instructions first.
+fn explain_line(tokens: &[Vec<u8>], decoder: Decoder) {
+    let explain_bytes = |bytes: &[u8]| {
+        for item in InstructionStream::with_decoder(bytes, 0, decoder) {
+            print!("{}", decoder.explain(first_word(item.bytes)));
+        }
+    };
+    let mut bytes = Vec::new();
+    for token in tokens {
+        if let [byte] = token[..] {
+            bytes.push(byte);
+            continue;
+        }
+        explain_bytes(&bytes);
+        bytes.clear();
+        print!("{}", decoder.explain(first_word(token)));
+    }
+    explain_bytes(&bytes);
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Output {
+    Plain,
+    Ansi,
+    Json,
+    Explain,
+}
+
+fn decode_line(line: &str, decoder: Decoder, options: &FormatOptions, output: Output) -> Result<(), String> {
+    let tokens = parse_line(line)?;
+    if output == Output::Explain {
+        explain_line(&tokens, decoder);
+        return Ok(());
+    }
+    let bytes = tokens.concat();
+    for item in InstructionStream::with_decoder(&bytes, 0, decoder) {
+        if output == Output::Json {
+            println!("{}", item.to_json(options));
+            continue;
+        }
+        let hex: String = item.bytes.iter().rev().map(|b| format!("{:02x}", b)).collect();
+        let format = InstructionFormat::of(first_word(item.bytes));
+        let options = &options.with_compressed(item.compressed());
+        let text = match &item.instruction {
+            Ok(inst) if output == Output::Ansi => inst.display_with(options, &Ansi).to_string(),
+            Ok(inst) => inst.display_with(options, &Plain).to_string(),
+            Err(err) => format!("error: {}", err),
+        };
+        println!("{:<8}  {}  {:<5}{}", hex, item.len, format, text);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut options = FormatOptions::default();
+    let mut decoder = Decoder::new();
+    let mut json = false;
+    let mut explain = false;
+    let mut words = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--objdump" => options.syntax = Syntax::Objdump,
+            "--no-aliases" => options = options.without_aliases(),
+            "--json" => json = true,
+            "--isa" => match args.next().map(|isa| Decoder::from_isa_string(&isa)) {
+                Some(Ok(isa)) => decoder = isa,
+                Some(Err(err)) => {
+                    eprintln!("rv64dec: {}", err);
+                    return ExitCode::FAILURE;
+                }
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            "explain" if words.is_empty() => explain = true,
+            _ => words.push(arg),
+        }
+    }
+
+    let output = match (json, std::io::stdout().is_terminal()) {
+        _ if explain => Output::Explain,
+        (true, _) => Output::Json,
+        (false, true) => Output::Ansi,
+        (false, false) => Output::Plain,
+    };
+    let lines: Box<dyn Iterator<Item = String>> = if words.is_empty() {
+        Box::new(std::io::stdin().lock().lines().map_while(Result::ok))
+    } else {
+        Box::new(std::iter::once(words.join(" ")))
+    };
+    let mut status = ExitCode::SUCCESS;
+    for line in lines {
+        if let Err(err) = decode_line(&line, decoder, &options, output) {
+            eprintln!("rv64dec: {}", err);
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
@@ -0,0 +1,111 @@
+//! Disassembles the executable sections of a RISC-V ELF file, like `objdump -d`.
+//! Intel HEX and S-record files are disassembled segment by segment, anything
+//! else is taken as a raw binary loaded at `--base`. `--json` and `--jsonl`
+//! print every instruction as a JSON object instead of a listing.
+//!
+//! Usage: `rv64dis [--no-aliases] [--json | --jsonl] [--isa <string>] [--base <address>] [--binary] <file>`
+
+use std::process::ExitCode;
+use rv64_decoder::{json_array, json_lines, Decoder, Elf, FormatOptions, Image, InstructionStream, StreamItem};
+
+const USAGE: &str = "usage: rv64dis [--no-aliases] [--json | --jsonl] [--isa <string>] [--base <address>] [--binary] <file>";
+
+fn parse_address(text: &str) -> Result<u64, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("invalid address `{}`", text))
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Output {
+    Listing,
+    Json,
+    JsonLines,
+}
+
+/// Prints the instructions of every code region as JSON, returns false if the output is a listing.
+fn print_json<'a>(output: Output, items: impl Iterator<Item = StreamItem<'a>>, options: &FormatOptions) -> bool {
+    match output {
+        Output::Listing => return false,
+        Output::Json => print!("{}", json_array(items, options)),
+        Output::JsonLines => print!("{}", json_lines(items, options)),
+    }
+    true
+}
+
+fn run() -> Result<(), String> {
+    let mut options = FormatOptions::objdump();
+    let mut isa = None;
+    let mut base = 0;
+    let mut binary = false;
+    let mut output = Output::Listing;
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-aliases" => options = options.without_aliases(),
+            "--isa" => isa = Some(args.next().ok_or(USAGE)?),
+            "--base" => base = parse_address(&args.next().ok_or(USAGE)?)?,
+            "--binary" => binary = true,
+            "--json" => output = Output::Json,
+            "--jsonl" => output = Output::JsonLines,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let path = path.ok_or(USAGE)?;
+    let isa = isa.map(|isa| Decoder::from_isa_string(&isa)).transpose()?;
+
+    let bytes = std::fs::read(&path).map_err(|err| format!("{}: {}", path, err))?;
+    if !binary && bytes.starts_with(b"\x7fELF") {
+        let elf = Elf::parse(&bytes).map_err(|err| format!("{}: {}", path, err))?;
+        let decoder = isa.unwrap_or_else(|| elf.decoder());
+        let code: Vec<_> = elf.sections.iter().filter(|section| section.is_executable() && !section.data.is_empty()).collect();
+        let items = code.iter().flat_map(|section| InstructionStream::with_decoder(section.data, section.address, decoder));
+        if print_json(output, items, &options) {
+            return Ok(());
+        }
+        println!("\n{}:     file format elf{}-littleriscv\n", path, elf.xlen);
+        for section in code {
+            println!("\nDisassembly of section {}:", section.name);
+            print!("{}", elf.listing(section).with_decoder(decoder).with_options(options));
+        }
+        return Ok(());
+    }
+
+    let image = match binary {
+        true => Image::from_binary(&bytes, base),
+        false => Image::parse(&bytes, base).map_err(|err| format!("{}: {}", path, err))?,
+    };
+    let decoder = isa.unwrap_or_default();
+    let items = image.segments.iter().flat_map(|segment| InstructionStream::with_decoder(&segment.data, segment.address, decoder));
+    if print_json(output, items, &options) {
+        return Ok(());
+    }
+    println!("\n{}:\n", path);
+    if let Some(entry) = image.entry {
+        println!("start address 0x{:x}", entry);
+    }
+    for segment in &image.segments {
+        let end = segment.address as u128 + segment.data.len() as u128;
+        println!("\nDisassembly of segment 0x{:x}..0x{:x}:\n", segment.address, end);
+        print!("{}", segment.listing().with_decoder(decoder).with_options(options));
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("rv64dis: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
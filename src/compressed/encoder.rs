@@ -0,0 +1,190 @@
+use crate::encoder::check_imm;
+use crate::instruction::Instruction;
+use crate::instruction::Instruction::*;
+use crate::register::Register;
+use crate::register::Register::*;
+
+const C0: u16 = 0b00;
+const C1: u16 = 0b01;
+const C2: u16 = 0b10;
+
+fn fit(imm: i64, bits: u32, align: i64, signed: bool) -> Option<u16> {
+    check_imm(imm, bits, align, signed).ok().map(|imm| imm as u16)
+}
+
+fn rvc(reg: Register) -> Option<u16> {
+    reg.to_rvc().map(|num| num as u16)
+}
+
+/// CI format with a 6-bit immediate split over bit 12 and bits [6:2].
+fn ci(funct3: u16, rd: Register, imm6: u16, op: u16) -> u16 {
+    funct3 << 13 | (imm6 >> 5 & 1) << 12 | (rd as u16) << 7 | (imm6 & 0b11111) << 2 | op
+}
+
+/// c.srli, c.srai and c.andi, which keep their funct2 in bits [11:10].
+fn cb_alu(funct2: u16, rd: u16, imm6: u16) -> u16 {
+    0b100 << 13 | (imm6 >> 5 & 1) << 12 | funct2 << 10 | rd << 7 | (imm6 & 0b11111) << 2 | C1
+}
+
+fn ca(funct6: u16, rd: Register, rs1: Register, rs2: Register, funct2: u16) -> Option<u16> {
+    if rd != rs1 {
+        return None;
+    }
+    Some(funct6 << 10 | rvc(rd)? << 7 | funct2 << 5 | rvc(rs2)? << 2 | C1)
+}
+
+fn cr(funct4: u16, rd_rs1: Register, rs2: Register) -> u16 {
+    funct4 << 12 | (rd_rs1 as u16) << 7 | (rs2 as u16) << 2 | C2
+}
+
+fn compress_addi(rd: Register, rs1: Register, imm: i64) -> Option<u16> {
+    if rd == Zero && rs1 == Zero && imm == 0 {
+        // c.nop
+        return Some(C1);
+    }
+    if rd == Zero || imm == 0 && rs1 != Zero {
+        return None;
+    }
+    if rs1 == Zero {
+        return Some(ci(0b010, rd, fit(imm, 6, 1, true)?, C1));
+    }
+    if rd == rs1 {
+        if let Some(imm6) = fit(imm, 6, 1, true) {
+            return Some(ci(0b000, rd, imm6, C1));
+        }
+        if rd == StackPointer {
+            let imm = fit(imm, 10, 16, true)?;
+            let imm9 = imm >> 9 & 1;
+            let imm4 = imm >> 4 & 1;
+            let imm6 = imm >> 6 & 1;
+            let imm8_7 = imm >> 7 & 0b11;
+            let imm5 = imm >> 5 & 1;
+            return Some(0b011 << 13 | imm9 << 12 | 2 << 7 | imm4 << 6 | imm6 << 5 | imm8_7 << 3 | imm5 << 2 | C1);
+        }
+    }
+    if rs1 == StackPointer {
+        let imm = fit(imm, 10, 4, false)?;
+        let imm5_4 = imm >> 4 & 0b11;
+        let imm9_6 = imm >> 6 & 0b1111;
+        let imm2 = imm >> 2 & 1;
+        let imm3 = imm >> 3 & 1;
+        return Some(imm5_4 << 11 | imm9_6 << 7 | imm2 << 6 | imm3 << 5 | rvc(rd)? << 2 | C0);
+    }
+    None
+}
+
+/// c.lw/c.sw and c.ld/c.sd, `width` is 4 or 8.
+fn compress_mem(funct3: u16, reg: Register, rs1: Register, imm: i64, width: i64) -> Option<u16> {
+    let imm = fit(imm, if width == 4 { 7 } else { 8 }, width, false)?;
+    let imm5_3 = imm >> 3 & 0b111;
+    let low = if width == 4 {
+        // uimm[2|6]
+        (imm >> 2 & 1) << 1 | imm >> 6 & 1
+    } else {
+        // uimm[7:6]
+        imm >> 6 & 0b11
+    };
+    Some(funct3 << 13 | imm5_3 << 10 | rvc(rs1)? << 7 | low << 5 | rvc(reg)? << 2 | C0)
+}
+
+/// c.lwsp and c.ldsp, `width` is 4 or 8.
+fn compress_load_sp(funct3: u16, rd: Register, imm: i64, width: i64) -> Option<u16> {
+    if rd == Zero {
+        return None;
+    }
+    let imm = fit(imm, if width == 4 { 8 } else { 9 }, width, false)?;
+    let imm5 = imm >> 5 & 1;
+    let low = if width == 4 {
+        // uimm[4:2|7:6]
+        (imm >> 2 & 0b111) << 2 | imm >> 6 & 0b11
+    } else {
+        // uimm[4:3|8:6]
+        (imm >> 3 & 0b11) << 3 | imm >> 6 & 0b111
+    };
+    Some(funct3 << 13 | imm5 << 12 | (rd as u16) << 7 | low << 2 | C2)
+}
+
+/// c.swsp and c.sdsp, `width` is 4 or 8.
+fn compress_store_sp(funct3: u16, rs2: Register, imm: i64, width: i64) -> Option<u16> {
+    let imm = fit(imm, if width == 4 { 8 } else { 9 }, width, false)?;
+    let field = if width == 4 {
+        // uimm[5:2|7:6]
+        (imm >> 2 & 0b1111) << 2 | imm >> 6 & 0b11
+    } else {
+        // uimm[5:3|8:6]
+        (imm >> 3 & 0b111) << 3 | imm >> 6 & 0b111
+    };
+    Some(funct3 << 13 | field << 7 | (rs2 as u16) << 2 | C2)
+}
+
+fn compress_branch(funct3: u16, rs1: Register, rs2: Register, imm: i64) -> Option<u16> {
+    if rs2 != Zero {
+        return None;
+    }
+    let imm = fit(imm, 9, 2, true)?;
+    let imm8 = imm >> 8 & 1;
+    let imm4_3 = imm >> 3 & 0b11;
+    let imm7_6 = imm >> 6 & 0b11;
+    let imm2_1 = imm >> 1 & 0b11;
+    let imm5 = imm >> 5 & 1;
+    Some(funct3 << 13 | imm8 << 12 | imm4_3 << 10 | rvc(rs1)? << 7 | imm7_6 << 5 | imm2_1 << 3 | imm5 << 2 | C1)
+}
+
+fn compress_j(imm: i64) -> Option<u16> {
+    let imm = fit(imm, 12, 2, true)?;
+    let imm11 = imm >> 11 & 1;
+    let imm4 = imm >> 4 & 1;
+    let imm9_8 = imm >> 8 & 0b11;
+    let imm10 = imm >> 10 & 1;
+    let imm6 = imm >> 6 & 1;
+    let imm7 = imm >> 7 & 1;
+    let imm3_1 = imm >> 1 & 0b111;
+    let imm5 = imm >> 5 & 1;
+    let field = imm11 << 10 | imm4 << 9 | imm9_8 << 7 | imm10 << 6 | imm6 << 5 | imm7 << 4 | imm3_1 << 1 | imm5;
+    Some(0b101 << 13 | field << 2 | C1)
+}
+
+impl Instruction {
+    /// Returns the RVC encoding of the instruction if it has one, so that
+    /// `decode(inst.compress()? as u32) == Ok(inst)`. HINT encodings are never produced.
+    pub fn compress(&self) -> Option<u16> {
+        match *self {
+            Addi { rd, rs1, imm } => compress_addi(rd, rs1, imm),
+            Addiw { rd, rs1, imm } if rd == rs1 && rd != Zero => Some(ci(0b001, rd, fit(imm, 6, 1, true)?, C1)),
+            Lui { rd, uimm } if rd != Zero && rd != StackPointer && uimm != 0 && uimm <= 0xFFFFF => {
+                let imm = (uimm as i64) << 44 >> 44;
+                Some(ci(0b011, rd, fit(imm, 6, 1, true)?, C1))
+            }
+            Slli { rd, rs1, shamt } if rd == rs1 && rd != Zero && shamt != 0 => Some(ci(0b000, rd, fit(shamt as i64, 6, 1, false)?, C2)),
+            Srli { rd, rs1, shamt } if rd == rs1 && shamt != 0 => Some(cb_alu(0b00, rvc(rd)?, fit(shamt as i64, 6, 1, false)?)),
+            Srai { rd, rs1, shamt } if rd == rs1 && shamt != 0 => Some(cb_alu(0b01, rvc(rd)?, fit(shamt as i64, 6, 1, false)?)),
+            Andi { rd, rs1, imm } if rd == rs1 => Some(cb_alu(0b10, rvc(rd)?, fit(imm, 6, 1, true)?)),
+
+            Sub { rd, rs1, rs2 } => ca(0b100011, rd, rs1, rs2, 0b00),
+            Xor { rd, rs1, rs2 } => ca(0b100011, rd, rs1, rs2, 0b01),
+            Or { rd, rs1, rs2 } => ca(0b100011, rd, rs1, rs2, 0b10),
+            And { rd, rs1, rs2 } => ca(0b100011, rd, rs1, rs2, 0b11),
+            Subw { rd, rs1, rs2 } => ca(0b100111, rd, rs1, rs2, 0b00),
+            Addw { rd, rs1, rs2 } => ca(0b100111, rd, rs1, rs2, 0b01),
+            Add { rd, rs1: Zero, rs2 } if rd != Zero && rs2 != Zero => Some(cr(0b1000, rd, rs2)),
+            Add { rd, rs1, rs2 } if rd == rs1 && rd != Zero && rs2 != Zero => Some(cr(0b1001, rd, rs2)),
+
+            Lw { rd, rs1: StackPointer, imm } => compress_load_sp(0b010, rd, imm, 4),
+            Ld { rd, rs1: StackPointer, imm } => compress_load_sp(0b011, rd, imm, 8),
+            Sw { rs1: StackPointer, rs2, imm } => compress_store_sp(0b110, rs2, imm, 4),
+            Sd { rs1: StackPointer, rs2, imm } => compress_store_sp(0b111, rs2, imm, 8),
+            Lw { rd, rs1, imm } => compress_mem(0b010, rd, rs1, imm, 4),
+            Ld { rd, rs1, imm } => compress_mem(0b011, rd, rs1, imm, 8),
+            Sw { rs1, rs2, imm } => compress_mem(0b110, rs2, rs1, imm, 4),
+            Sd { rs1, rs2, imm } => compress_mem(0b111, rs2, rs1, imm, 8),
+
+            Jal { rd: Zero, imm } => compress_j(imm),
+            Jalr { rd: Zero, rs1, imm: 0 } if rs1 != Zero => Some(cr(0b1000, rs1, Zero)),
+            Jalr { rd: ReturnAddress, rs1, imm: 0 } if rs1 != Zero => Some(cr(0b1001, rs1, Zero)),
+            Beq { rs1, rs2, imm } => compress_branch(0b110, rs1, rs2, imm),
+            Bne { rs1, rs2, imm } => compress_branch(0b111, rs1, rs2, imm),
+            Ebreak => Some(cr(0b1001, Zero, Zero)),
+            _ => None,
+        }
+    }
+}
@@ -1,9 +1,11 @@
 mod optype;
+mod encoder;
 
 use crate::compressed::optype::COpType;
 use crate::{Instruction, Register};
+use crate::error::{DecodeError, Field};
 
-fn decode_addi4spn(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_addi4spn(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_ciw(full_opcode) {
         COpType::CIW { rd, .. } => {
             let imm2   = (full_opcode>>4) & 0b0000000100;
@@ -12,7 +14,10 @@ fn decode_addi4spn(full_opcode: u16) -> Result<Instruction, String> {
             let imm9_6 = (full_opcode>>1) & 0b1111000000;
             let imm = (imm9_6 | imm5_4 | imm3 | imm2) as u64 as i64;
             if imm == 0 {
-                return Err("Addi4spn requires non-zero immediate.".to_string());
+                if full_opcode == 0 {
+                    return Err(DecodeError::illegal(0, Field::Imm, 0));
+                }
+                return Err(DecodeError::reserved(full_opcode as u32, Field::Imm, 0));
             }
             Ok(Instruction::Addi { rd, rs1: Register::StackPointer, imm, })
         }
@@ -20,7 +25,7 @@ fn decode_addi4spn(full_opcode: u16) -> Result<Instruction, String> {
     }
 }
 
-fn decode_sd(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_sd(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_cs(full_opcode) {
         COpType::CS { rs1, rs2, .. } => {
             let imm5_3 = full_opcode >> 7 & 0b00111000;
@@ -32,36 +37,30 @@ fn decode_sd(full_opcode: u16) -> Result<Instruction, String> {
     }
 }
 
-fn decode_addi(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_addi(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_ci(full_opcode) {
         COpType::CI { rd_rs1, .. } => {
-            if rd_rs1 == Register::Zero {
-                return Err("Dest for c.addi cannot be x0!".to_string());
-            }
-            let imm = ((full_opcode as i16 >> 2) & 0b11111 | ((full_opcode << 3) as i16 >> 10)) as i64;
-            if imm == 0 {
-                Err("Immediate for c.addi cannot be 0!".to_string())
-            } else {
-                Ok(Instruction::Addi { rd: rd_rs1, rs1: rd_rs1, imm, })
-            }
+            let imm = ((full_opcode as i16 >> 2) & 0b11111 | ((full_opcode << 3) as i16 >> 10) & !0b11111) as i64;
+            // c.nop when both are zero, HINTs when only one of them is
+            Ok(Instruction::Addi { rd: rd_rs1, rs1: rd_rs1, imm, })
         }
         _ => unreachable!(),
     }
 }
-fn decode_addiw(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_addiw(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_ci(full_opcode) {
         COpType::CI { rd_rs1, .. } => {
             if rd_rs1 == Register::Zero {
-                return Err("Dest for c.addi cannot be x0!".to_string());
+                return Err(DecodeError::reserved(full_opcode as u32, Field::Rd, 0));
             }
-            let imm = ((full_opcode as i16 >> 2) & 0b11111 | ((full_opcode << 3) as i16 >> 10)) as i64;
+            let imm = ((full_opcode as i16 >> 2) & 0b11111 | ((full_opcode << 3) as i16 >> 10) & !0b11111) as i64;
             Ok(Instruction::Addiw { rd: rd_rs1, rs1: rd_rs1, imm, })
         }
         _ => unreachable!(),
     }
 }
 
-fn decode_li_lui_addi16spn(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_li_lui_addi16spn(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_ci(full_opcode) {
         COpType::CI { rd_rs1, funct3 } => {
             let imm4_0 = (full_opcode >> 2 & 0b11111) as u8;
@@ -69,16 +68,9 @@ fn decode_li_lui_addi16spn(full_opcode: u16) -> Result<Instruction, String> {
             let sign_ext = if imm5 != 0 {0b11000000u8} else {0};
             let imm = (sign_ext | imm5 | imm4_0) as i8 as i64;
             match funct3 {
-                0b010 => {
-                    if rd_rs1 == Register::Zero {
-                        return Err("c.li rd can not be zero!".to_string());
-                    }
-                    Ok(Instruction::Addi { rd: rd_rs1, rs1: Register::Zero, imm })
-                }
+                // rd == zero is a HINT for both c.li and c.lui
+                0b010 => Ok(Instruction::Addi { rd: rd_rs1, rs1: Register::Zero, imm }),
                 0b011 => {
-                    if rd_rs1 == Register::Zero {
-                        return Err("c.lui rd can not be zero!".to_string());
-                    }
                     if rd_rs1 == Register::StackPointer {
                         let imm4   = (full_opcode>>2) & 0b0000010000;
                         let imm5   = (full_opcode<<3) & 0b0000100000;
@@ -87,19 +79,24 @@ fn decode_li_lui_addi16spn(full_opcode: u16) -> Result<Instruction, String> {
                         let imm9   = (full_opcode>>3) & 0b1000000000;
                         let sign_ext = if imm9 == 0 { 0 } else { 0b1111110000000000u16 };
                         let imm = (sign_ext | imm9 | imm8_7 | imm6 | imm5 | imm4) as i16 as i64;
+                        if imm == 0 {
+                            return Err(DecodeError::reserved(full_opcode as u32, Field::Imm, 0));
+                        }
                         Ok(Instruction::Addi { rd: Register::StackPointer, rs1: Register::StackPointer, imm, })
+                    } else if imm == 0 {
+                        Err(DecodeError::reserved(full_opcode as u32, Field::Imm, 0))
                     } else {
-                        Ok(Instruction::Lui { rd: rd_rs1, uimm: imm as u64 })
+                        Ok(Instruction::Lui { rd: rd_rs1, uimm: imm as u64 & 0xFFFFF })
                     }
                 }
-                _ => todo!("TODO: Implement decode_li funct3: 0b{:03b}", funct3)
+                _ => unreachable!()
             }
         }
         _ => unreachable!()
     }
 }
 
-fn decode_ld(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_ld(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_cl(full_opcode) {
         COpType::CL { rd, rs1, .. } => {
             let imm5_3 = full_opcode >> 7 & 0b00111000;
@@ -111,40 +108,111 @@ fn decode_ld(full_opcode: u16) -> Result<Instruction, String> {
     }
 }
 
-fn decode_jr(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_lw(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    match COpType::new_cl(full_opcode) {
+        COpType::CL { rd, rs1, .. } => {
+            let imm2 = full_opcode >> 4 & 0b0000100;
+            let imm5_3 = full_opcode >> 7 & 0b0111000;
+            let imm6 = full_opcode << 1 & 0b1000000;
+            let imm = (imm6 | imm5_3 | imm2) as u64 as i64;
+            Ok(Instruction::Lw { rd, rs1, imm, })
+        }
+        _ => unreachable!()
+    }
+}
+
+fn decode_sw(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    match COpType::new_cs(full_opcode) {
+        COpType::CS { rs1, rs2, .. } => {
+            let imm2 = full_opcode >> 4 & 0b0000100;
+            let imm5_3 = full_opcode >> 7 & 0b0111000;
+            let imm6 = full_opcode << 1 & 0b1000000;
+            let imm = (imm6 | imm5_3 | imm2) as u64 as i64;
+            Ok(Instruction::Sw { rs1, rs2, imm, })
+        }
+        _ => unreachable!()
+    }
+}
+
+fn decode_lwsp(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    match COpType::new_ci(full_opcode) {
+        COpType::CI { rd_rs1, ..} => {
+            if rd_rs1 == Register::Zero {
+                return Err(DecodeError::reserved(full_opcode as u32, Field::Rd, 0));
+            }
+            let imm4_2 = (full_opcode>>2) &0b00011100;
+            let imm5 = (full_opcode>>7)   &0b00100000;
+            let imm7_6 = (full_opcode<<4) &0b11000000;
+            let imm = (imm7_6 | imm5 | imm4_2) as i64;
+            Ok(Instruction::Lw {
+                rd: rd_rs1,
+                rs1: Register::StackPointer,
+                imm,
+            })
+        }
+        _ => unreachable!()
+    }
+}
+
+fn decode_swsp(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    match COpType::new_css(full_opcode) {
+        COpType::CSS { rs2, .. } => {
+            let imm7_6 = (full_opcode>>1) &0b11000000;
+            let imm5_2 = (full_opcode>>7) &0b00111100;
+            let imm = (imm7_6 | imm5_2) as i64;
+            Ok(Instruction::Sw {
+                rs1: Register::StackPointer,
+                rs2,
+                imm,
+            })
+        }
+        _ => unreachable!()
+    }
+}
+
+fn decode_slli(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    match COpType::new_ci(full_opcode) {
+        COpType::CI { rd_rs1, .. } => {
+            let shamt = ((full_opcode >> 7) & 0b100000 | (full_opcode >> 2) & 0b11111) as u64;
+            // rd == zero and shamt == 0 are HINTs
+            Ok(Instruction::Slli { rd: rd_rs1, rs1: rd_rs1, shamt })
+        }
+        _ => unreachable!()
+    }
+}
+
+fn decode_jr(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_cr(full_opcode) {
         COpType::CR { rd_rs1, rs2, funct4 } => {
             match funct4 {
+                // c.mv and c.add with rd == zero are HINTs
                 0b1000 => {
-                    if rd_rs1 == Register::Zero {
-                        Err("c.jr/c.mv/etc. rs1 has to be non-zero".to_string())
-                    }
-                    else if rs2 == Register::Zero {
-                        Ok(Instruction::Jalr { rd: Register::Zero, rs1: rd_rs1, imm: 0 })
-                    } else {
+                    if rs2 != Register::Zero {
                         Ok(Instruction::Add { rd: rd_rs1, rs1: Register::Zero, rs2, })
+                    } else if rd_rs1 == Register::Zero {
+                        Err(DecodeError::reserved(full_opcode as u32, Field::Rs1, 0))
+                    } else {
+                        Ok(Instruction::Jalr { rd: Register::Zero, rs1: rd_rs1, imm: 0 })
                     }
                 },
                 0b1001 => {
-                    if rd_rs1 == Register::Zero {
+                    if rs2 != Register::Zero {
+                        Ok(Instruction::Add { rd: rd_rs1, rs1: rd_rs1, rs2, })
+                    } else if rd_rs1 == Register::Zero {
                         Ok(Instruction::Ebreak)
                     } else {
-                        println!("LOL\n\n\n\n");
-                        if rs2 == Register::Zero {
-                            Ok(Instruction::Jalr { rd: Register::ReturnAddress, rs1: rd_rs1, imm: 0 })
-                        } else {
-                            Ok(Instruction::Add { rd: rd_rs1, rs1: rd_rs1, rs2, })
-                        }
+                        Ok(Instruction::Jalr { rd: Register::ReturnAddress, rs1: rd_rs1, imm: 0 })
                     }
                 }
-                _ => Err(format!("Unknown cr funct4: 0b{funct4:04b}"))
+                _ => unreachable!()
             }
         }
         _ => unreachable!(),
     }
 }
 
-fn decode_j(full_opcode: u16) -> Result<Instruction, String> {
+/// The offset of c.j and c.jal.
+fn cj_imm(full_opcode: u16) -> i64 {
     let imm3_1 = (full_opcode>>2) &0b000000001110;
     let imm4 = (full_opcode>>7)   &0b000000010000;
     let imm5 = (full_opcode<<3)   &0b000000100000;
@@ -154,11 +222,14 @@ fn decode_j(full_opcode: u16) -> Result<Instruction, String> {
     let imm10 = (full_opcode<<2)  &0b010000000000;
     let imm11 = (full_opcode>>1)  &0b1000_0000_0000;
     let sign_ext = if imm11 > 0 {0xF000} else {0};
-    let imm = (sign_ext | imm3_1 | imm4 | imm5 | imm6 | imm7 | imm9_8 | imm10 | imm11) as i16 as i64;
-    Ok(Instruction::Jal { rd: Register::Zero, imm })
+    (sign_ext | imm3_1 | imm4 | imm5 | imm6 | imm7 | imm9_8 | imm10 | imm11) as i16 as i64
+}
+
+fn decode_j(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    Ok(Instruction::Jal { rd: Register::Zero, imm: cj_imm(full_opcode) })
 }
 
-fn decode_beqz_bnez(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_beqz_bnez(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_cb(full_opcode) {
         COpType::CB { rs1, funct3} => {
             let imm2_1 = (full_opcode>>2) &0b000000110;
@@ -186,12 +257,12 @@ fn decode_beqz_bnez(full_opcode: u16) -> Result<Instruction, String> {
     }
 }
 
-fn decode_sdsp(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_sdsp(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_css(full_opcode) {
         COpType::CSS { rs2, .. } => {
-            let imm7_6 = (full_opcode>>1) &0b11000000;
-            let imm5_3 = (full_opcode>>7) &0b00111000;
-            let imm = (imm7_6 | imm5_3) as i64;
+            let imm8_6 = (full_opcode>>1) &0b111000000;
+            let imm5_3 = (full_opcode>>7) &0b000111000;
+            let imm = (imm8_6 | imm5_3) as i64;
             Ok(Instruction::Sd {
                 rs1: Register::StackPointer,
                 rs2,
@@ -202,9 +273,12 @@ fn decode_sdsp(full_opcode: u16) -> Result<Instruction, String> {
     }
 }
 
-fn decode_ldsp(full_opcode: u16) -> Result<Instruction, String> {
+fn decode_ldsp(full_opcode: u16) -> Result<Instruction, DecodeError> {
     match COpType::new_ci(full_opcode) {
         COpType::CI { rd_rs1, ..} => {
+            if rd_rs1 == Register::Zero {
+                return Err(DecodeError::reserved(full_opcode as u32, Field::Rd, 0));
+            }
             let imm4_3 = (full_opcode>>2) &0b000011000;
             let imm5 = (full_opcode>>7)   &0b000100000;
             let imm8_6 = (full_opcode<<4) &0b111000000;
@@ -219,26 +293,47 @@ fn decode_ldsp(full_opcode: u16) -> Result<Instruction, String> {
     }
 }
 
-fn decode_math(full_opcode: u16) -> Result<Instruction, String> {
-    match COpType::new_ca(full_opcode) {
-        COpType::CA { rd_rs1, rs2, funct6, funct2 } => {
-            match (funct6<<2) | funct2 {
-                0b10001110 => {
-                    Ok(Instruction::Or {
-                        rd: rd_rs1,
-                        rs1: rd_rs1,
-                        rs2,
-                    })
+fn decode_math(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    match (full_opcode >> 10) & 0b11 {
+        0b00..=0b10 => {
+            match COpType::new_cb(full_opcode) {
+                COpType::CB { rs1, .. } => {
+                    let shamt = ((full_opcode >> 7) & 0b100000 | (full_opcode >> 2) & 0b11111) as u64;
+                    match (full_opcode >> 10) & 0b11 {
+                        0b10 => {
+                            let imm = ((full_opcode as i16 >> 2) & 0b11111 | ((full_opcode << 3) as i16 >> 10) & !0b11111) as i64;
+                            Ok(Instruction::Andi { rd: rs1, rs1, imm })
+                        }
+                        // shamt == 0 is a HINT
+                        0b00 => Ok(Instruction::Srli { rd: rs1, rs1, shamt }),
+                        _ => Ok(Instruction::Srai { rd: rs1, rs1, shamt }),
+                    }
                 }
-                funct8 => unimplemented!("Unimplemented compressed math funct6+2: 0b{:08b}", funct8)
+                _ => unreachable!()
+            }
+        }
+        _ => {
+            match COpType::new_ca(full_opcode) {
+                COpType::CA { rd_rs1, rs2, funct6, funct2 } => {
+                    let rd = rd_rs1;
+                    let rs1 = rd_rs1;
+                    match (funct6<<2) | funct2 {
+                        0b10001100 => Ok(Instruction::Sub { rd, rs1, rs2 }),
+                        0b10001101 => Ok(Instruction::Xor { rd, rs1, rs2 }),
+                        0b10001110 => Ok(Instruction::Or { rd, rs1, rs2 }),
+                        0b10001111 => Ok(Instruction::And { rd, rs1, rs2 }),
+                        0b10011100 => Ok(Instruction::Subw { rd, rs1, rs2 }),
+                        0b10011101 => Ok(Instruction::Addw { rd, rs1, rs2 }),
+                        _ => Err(DecodeError::reserved(full_opcode as u32, Field::Funct2, funct2 as u32))
+                    }
+                }
+                _ => unreachable!()
             }
         }
-        _ => unreachable!()
     }
 }
 
-// TODO: Should this be pub(crate)?
-pub(crate) fn decode_compressed(full_opcode: u16) -> Result<Instruction, String> {
+pub(crate) fn decode_compressed(full_opcode: u16) -> Result<Instruction, DecodeError> {
     let funct3 = (full_opcode & 0xE000) >> 11;
     match funct3 | (full_opcode & 0b11) {
         0b00000 => decode_addi4spn(full_opcode),
@@ -253,6 +348,75 @@ pub(crate) fn decode_compressed(full_opcode: u16) -> Result<Instruction, String>
         0b11110 => decode_sdsp(full_opcode),
         0b01110 => decode_ldsp(full_opcode),
         0b10001 => decode_math(full_opcode),
-        op => Err(format!("TODO: Implement compressed opcode 0b{op:05b}"))
+        0b01000 => decode_lw(full_opcode),
+        0b11000 => decode_sw(full_opcode),
+        0b01010 => decode_lwsp(full_opcode),
+        0b11010 => decode_swsp(full_opcode),
+        0b00010 => decode_slli(full_opcode),
+        0b10000 => Err(DecodeError::reserved(full_opcode as u32, Field::Funct3, 0b100)),
+        _ => Err(DecodeError::unimplemented(full_opcode as u32))
+    }
+}
+
+/// Decodes an RV32 compressed instruction. The slots RV64 uses for c.ld, c.sd,
+/// c.ldsp and c.sdsp hold the F loads and stores, which aren't decoded, and
+/// c.addiw is c.jal. Shifts by 32 or more are reserved for custom extensions.
+pub(crate) fn decode_compressed_rv32(full_opcode: u16) -> Result<Instruction, DecodeError> {
+    let funct3 = (full_opcode & 0xE000) >> 11;
+    let shamt5 = (full_opcode >> 12) & 1 == 1;
+    match funct3 | (full_opcode & 0b11) {
+        0b00101 => Ok(Instruction::Jal { rd: Register::ReturnAddress, imm: cj_imm(full_opcode) }),
+        0b01100 | 0b11100 | 0b01110 | 0b11110 => Err(DecodeError::unimplemented(full_opcode as u32)),
+        0b00010 if shamt5 => Err(DecodeError::reserved(full_opcode as u32, Field::Shamt, 0b100000 | (full_opcode as u32 >> 2) & 0b11111)),
+        0b10001 if shamt5 && (full_opcode >> 10) & 0b11 < 0b10 => {
+            Err(DecodeError::reserved(full_opcode as u32, Field::Shamt, 0b100000 | (full_opcode as u32 >> 2) & 0b11111))
+        }
+        // c.subw and c.addw
+        0b10001 if shamt5 && (full_opcode >> 10) & 0b11 == 0b11 && (full_opcode >> 5) & 0b11 < 0b10 => {
+            Err(DecodeError::reserved(full_opcode as u32, Field::Funct2, (full_opcode as u32 >> 5) & 0b11))
+        }
+        _ => decode_compressed(full_opcode),
+    }
+}
+
+/// Returns the RVC mnemonic `full_opcode` was written as, given what it expanded to.
+pub(crate) fn compressed_mnemonic(full_opcode: u16, inst: &Instruction) -> &'static str {
+    let funct3 = (full_opcode & 0xE000) >> 11;
+    match (funct3 | (full_opcode & 0b11), inst) {
+        (0b00000, _) => "c.addi4spn",
+        (0b01100, _) => "c.ld",
+        (0b11100, _) => "c.sd",
+        (0b00001, Instruction::Addi { rd: Register::Zero, imm: 0, .. }) => "c.nop",
+        (0b00001, _) => "c.addi",
+        (0b00101, Instruction::Jal { .. }) => "c.jal",
+        (0b00101, _) => "c.addiw",
+        (0b01001, _) => "c.li",
+        (0b01101, Instruction::Addi { .. }) => "c.addi16sp",
+        (0b01101, _) => "c.lui",
+        (0b01000, _) => "c.lw",
+        (0b11000, _) => "c.sw",
+        (0b01010, _) => "c.lwsp",
+        (0b11010, _) => "c.swsp",
+        (0b00010, _) => "c.slli",
+        (0b10001, Instruction::Srli { .. }) => "c.srli",
+        (0b10001, Instruction::Srai { .. }) => "c.srai",
+        (0b10001, Instruction::Andi { .. }) => "c.andi",
+        (0b10001, Instruction::Sub { .. }) => "c.sub",
+        (0b10001, Instruction::Xor { .. }) => "c.xor",
+        (0b10001, Instruction::Or { .. }) => "c.or",
+        (0b10001, Instruction::And { .. }) => "c.and",
+        (0b10001, Instruction::Subw { .. }) => "c.subw",
+        (0b10001, Instruction::Addw { .. }) => "c.addw",
+        (0b10010, Instruction::Jalr { rd: Register::Zero, .. }) => "c.jr",
+        (0b10010, Instruction::Jalr { .. }) => "c.jalr",
+        (0b10010, Instruction::Ebreak) => "c.ebreak",
+        (0b10010, Instruction::Add { .. }) if full_opcode & 0x1000 == 0 => "c.mv",
+        (0b10010, _) => "c.add",
+        (0b10101, _) => "c.j",
+        (0b11001, _) => "c.beqz",
+        (0b11101, _) => "c.bnez",
+        (0b01110, _) => "c.ldsp",
+        (0b11110, _) => "c.sdsp",
+        _ => unreachable!(),
     }
 }
\ No newline at end of file
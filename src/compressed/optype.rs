@@ -1,5 +1,6 @@
 use crate::Register;
 
+#[allow(dead_code, clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub(crate) enum COpType {
     CR { rd_rs1: Register, rs2: Register, funct4: u8 },
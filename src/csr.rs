@@ -3,12 +3,118 @@ use crate::csr::CsrRegister::*;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CsrRegister {
-    // idk
+    // Unprivileged floating-point
+    FFlags, // 0x001
+    FRM, // 0x002
+    FCSR, // 0x003
+
+    // Unprivileged vector
+    VStart, // 0x008
+    VXSat, // 0x009
+    VXRM, // 0x00A
+    VCSR, // 0x00F
+    VL, // 0xC20
+    VType, // 0xC21
+    VLenB, // 0xC22
+
+    // Unprivileged counters/timers
+    Cycle, // 0xC00
+    Time, // 0xC01
+    InstRet, // 0xC02
+    HPMCounter(u8), // 0xC03 - 0xC1F
+    //CycleH, // 0xC80 RV32 ONLY
+
+    // Supervisor trap setup
+    SStatus, // 0x100
+    SIE, // 0x104
+    STVec, // 0x105
+    SCounterEn, // 0x106
+
+    // Supervisor configuration
+    SEnvCfg, // 0x10A
+
+    // Supervisor trap handling
     SScratch, // 0x140
     SepC, // 0x141
+    SCause, // 0x142
+    STVal, // 0x143
+    SIP, // 0x144
 
-    // Trap setup
-    SStatus, // 0x100
+    // Supervisor protection and translation
+    SATP, // 0x180
+
+    // Supervisor debug/trace
+    SContext, // 0x5A8
+
+    // Hypervisor trap setup
+    HStatus, // 0x600
+    HEDeleg, // 0x602
+    HIDeleg, // 0x603
+    HIE, // 0x604
+    HCounterEn, // 0x606
+    HGEIE, // 0x607
+    //HEDelegH, // 0x612 RV32 ONLY
+
+    // Hypervisor trap handling
+    HTVal, // 0x643
+    HIP, // 0x644
+    HVIP, // 0x645
+    HTInst, // 0x64A
+    HGEIP, // 0xE12
+
+    // Hypervisor configuration
+    HEnvCfg, // 0x60A
+    //HEnvCfgH, // 0x61A RV32 ONLY
+
+    // Hypervisor protection and translation
+    HGATP, // 0x680
+
+    // Hypervisor debug/trace
+    HContext, // 0x6A8
+
+    // Hypervisor counter/timer virtualization
+    HTimeDelta, // 0x605
+    //HTimeDeltaH, // 0x615 RV32 ONLY
+
+    // Supervisor timer (Sstc)
+    STimeCmp, // 0x14D
+    VSTimeCmp, // 0x24D
+    //STimeCmpH, // 0x15D RV32 ONLY
+    //VSTimeCmpH, // 0x25D RV32 ONLY
+
+    // Advanced interrupt architecture (Smaia/Ssaia)
+    MISelect, // 0x350
+    MIReg, // 0x351
+    MTopEI, // 0x35C
+    MTopI, // 0xFB0
+    MVIEn, // 0x308
+    MVIP, // 0x309
+    SISelect, // 0x150
+    SIReg, // 0x151
+    STopEI, // 0x15C
+    STopI, // 0xDB0
+    HVIEn, // 0x608
+    HVICtl, // 0x609
+    HVIPrio1, // 0x646
+    HVIPrio2, // 0x647
+    VSISelect, // 0x250
+    VSIReg, // 0x251
+    VSTopEI, // 0x25C
+    VSTopI, // 0xEB0
+    //MIDelegH, MIEH, MVIEnH, MVIPH, MIPH, // 0x313, 0x314, 0x318, 0x319, 0x354 RV32 ONLY
+
+    // Virtual supervisor registers
+    VSStatus, // 0x200
+    VSIE, // 0x204
+    VSTVec, // 0x205
+    VSScratch, // 0x240
+    VSEPC, // 0x241
+    VSCause, // 0x242
+    VSTVal, // 0x243
+    VSIP, // 0x244
+    VSATP, // 0x280
+
+    // Machine trap setup
     MStatus, // 0x300
     MISA, // 0x301
     MEDeleg, // 0x302
@@ -18,7 +124,6 @@ pub enum CsrRegister {
     MCounterEn, // 0x306
     //MStatusH, // 0x310 RV32 ONLY
 
-    SIE, // idk
     MPIE, // idk
     SPIE, // idk
 
@@ -35,18 +140,63 @@ pub enum CsrRegister {
     PMPCfg(u8), // 0x3A0 - 0x3AF
     PMPAddr(u8), // 0x3B0 - 0x3EF
 
+    // Machine counters/timers
+    MCycle, // 0xB00
+    MInstRet, // 0xB02
+    MHPMCounter(u8), // 0xB03 - 0xB1F
+    //MCycleH, // 0xB80 RV32 ONLY
+
+    // Machine counter setup
+    MCountInhibit, // 0x320
+    MHPMEvent(u8), // 0x323 - 0x33F
+
     // General machine info
     MVendorID, // 0xF11
     MArchID, // 0xF12
     MImpID, // 0xF13
     MHartID, // 0xF14
 
+    // Resumable non-maskable interrupts (Smrnmi)
+    MNScratch, // 0x740
+    MNEPC, // 0x741
+    MNCause, // 0x742
+    MNStatus, // 0x744
+
+    // Debug/trace triggers
+    TSelect, // 0x7A0
+    TData1, // 0x7A1
+    TData2, // 0x7A2
+    TData3, // 0x7A3
+    TInfo, // 0x7A4
+    TControl, // 0x7A5
+    MContext, // 0x7A8
+
+    // Debug mode
+    DCSR, // 0x7B0
+    DPC, // 0x7B1
+    DScratch0, // 0x7B2
+    DScratch1, // 0x7B3
+
     // Unknowns
     Other(u16),
     Invalid(u64),
 }
 
 impl CsrRegister {
+    /// The lowercase CSR name, e.g. `mstatus` or `pmpcfg0`, without colors.
+    pub fn name(&self) -> String {
+        match self {
+            Other(reg) => format!("UNKNOWN(0x{:0X})", reg),
+            Invalid(reg) => format!("INVALID(0x{:0X})", reg),
+            PMPCfg(num) => format!("pmpcfg{}", num),
+            PMPAddr(num) => format!("pmpaddr{}", num),
+            HPMCounter(num) => format!("hpmcounter{}", num),
+            MHPMCounter(num) => format!("mhpmcounter{}", num),
+            MHPMEvent(num) => format!("mhpmevent{}", num),
+            _ => format!("{:?}", self).to_lowercase(),
+        }
+    }
+
     /// Returns a mask of the register bits.
     pub fn mask(&self) -> u64 {
         match self {
@@ -58,12 +208,23 @@ impl CsrRegister {
         }
     }
 
+    /// Returns the `*iselect` CSR choosing which register an `*ireg` window accesses.
+    pub fn indirect_select(&self) -> Option<CsrRegister> {
+        match self {
+            MIReg => Some(MISelect),
+            SIReg => Some(SISelect),
+            VSIReg => Some(VSISelect),
+            _ => None,
+        }
+    }
+
     /// Returns true if the register is writable.
+    /// CSRs with address bits [11:10] set to 0b11 are read-only.
     pub fn is_writable(&self) -> bool {
         match self {
-            MVendorID | MArchID | MImpID | MHartID => false,
             Invalid(_) => false,
-            _ => true,
+            MPIE | SPIE => true,
+            _ => (usize::from(*self) >> 10) & 0b11 != 0b11,
         }
     }
 }
@@ -71,9 +232,84 @@ impl CsrRegister {
 impl From<CsrRegister> for usize {
     fn from(csr: CsrRegister) -> Self {
         match csr {
+            FFlags => 0x001,
+            FRM => 0x002,
+            FCSR => 0x003,
+
+            VStart => 0x008,
+            VXSat => 0x009,
+            VXRM => 0x00A,
+            VCSR => 0x00F,
+            VL => 0xC20,
+            VType => 0xC21,
+            VLenB => 0xC22,
+
+            Cycle => 0xC00,
+            Time => 0xC01,
+            InstRet => 0xC02,
+            HPMCounter(num) => num as usize + 0xC00,
+
+            SStatus => 0x100,
+            SIE => 0x104,
+            STVec => 0x105,
+            SCounterEn => 0x106,
+            SEnvCfg => 0x10A,
             SScratch => 0x140,
             SepC => 0x141,
-            SStatus => 0x100,
+            SCause => 0x142,
+            STVal => 0x143,
+            SIP => 0x144,
+            SATP => 0x180,
+            SContext => 0x5A8,
+
+            HStatus => 0x600,
+            HEDeleg => 0x602,
+            HIDeleg => 0x603,
+            HIE => 0x604,
+            HCounterEn => 0x606,
+            HGEIE => 0x607,
+            HTVal => 0x643,
+            HIP => 0x644,
+            HVIP => 0x645,
+            HTInst => 0x64A,
+            HGEIP => 0xE12,
+            HEnvCfg => 0x60A,
+            HGATP => 0x680,
+            HContext => 0x6A8,
+            HTimeDelta => 0x605,
+
+            STimeCmp => 0x14D,
+            VSTimeCmp => 0x24D,
+
+            MISelect => 0x350,
+            MIReg => 0x351,
+            MTopEI => 0x35C,
+            MTopI => 0xFB0,
+            MVIEn => 0x308,
+            MVIP => 0x309,
+            SISelect => 0x150,
+            SIReg => 0x151,
+            STopEI => 0x15C,
+            STopI => 0xDB0,
+            HVIEn => 0x608,
+            HVICtl => 0x609,
+            HVIPrio1 => 0x646,
+            HVIPrio2 => 0x647,
+            VSISelect => 0x250,
+            VSIReg => 0x251,
+            VSTopEI => 0x25C,
+            VSTopI => 0xEB0,
+
+            VSStatus => 0x200,
+            VSIE => 0x204,
+            VSTVec => 0x205,
+            VSScratch => 0x240,
+            VSEPC => 0x241,
+            VSCause => 0x242,
+            VSTVal => 0x243,
+            VSIP => 0x244,
+            VSATP => 0x280,
+
             MStatus => 0x300,
             MISA => 0x301,
             MEDeleg => 0x302,
@@ -86,7 +322,6 @@ impl From<CsrRegister> for usize {
             // TODO: uuhhhhhhh
             MPIE => 0xFFFFFFFFFFFFFFFF,
             SPIE => 0xFFFFFFFFFFFFFFFF,
-            SIE => 0xFFFFFFFFFFFFFFFF,
 
             MScratch => 0x340,
             MEPC => 0x341,
@@ -98,11 +333,35 @@ impl From<CsrRegister> for usize {
             PMPCfg(num) => num as usize + 0x3A0,
             PMPAddr(num) => num as usize + 0x3B0,
 
+            MCycle => 0xB00,
+            MInstRet => 0xB02,
+            MHPMCounter(num) => num as usize + 0xB00,
+            MCountInhibit => 0x320,
+            MHPMEvent(num) => num as usize + 0x320,
+
             MVendorID => 0xF11,
             MArchID => 0xF12,
             MImpID => 0xF13,
             MHartID => 0xF14,
 
+            MNScratch => 0x740,
+            MNEPC => 0x741,
+            MNCause => 0x742,
+            MNStatus => 0x744,
+
+            TSelect => 0x7A0,
+            TData1 => 0x7A1,
+            TData2 => 0x7A2,
+            TData3 => 0x7A3,
+            TInfo => 0x7A4,
+            TControl => 0x7A5,
+            MContext => 0x7A8,
+
+            DCSR => 0x7B0,
+            DPC => 0x7B1,
+            DScratch0 => 0x7B2,
+            DScratch1 => 0x7B3,
+
             Other(n) => n as usize,
             Invalid(_) => (-1_i64) as usize,
         }
@@ -112,9 +371,84 @@ impl From<CsrRegister> for usize {
 impl From<u64> for CsrRegister {
     fn from(num: u64) -> Self {
         match num {
+            0x001 => FFlags,
+            0x002 => FRM,
+            0x003 => FCSR,
+
+            0x008 => VStart,
+            0x009 => VXSat,
+            0x00A => VXRM,
+            0x00F => VCSR,
+            0xC20 => VL,
+            0xC21 => VType,
+            0xC22 => VLenB,
+
+            0xC00 => Cycle,
+            0xC01 => Time,
+            0xC02 => InstRet,
+            0xC03..=0xC1F => HPMCounter((num - 0xC00) as u8),
+
+            0x100 => SStatus,
+            0x104 => SIE,
+            0x105 => STVec,
+            0x106 => SCounterEn,
+            0x10A => SEnvCfg,
             0x140 => SScratch,
             0x141 => SepC,
-            0x100 => SStatus,
+            0x142 => SCause,
+            0x143 => STVal,
+            0x144 => SIP,
+            0x180 => SATP,
+            0x5A8 => SContext,
+
+            0x600 => HStatus,
+            0x602 => HEDeleg,
+            0x603 => HIDeleg,
+            0x604 => HIE,
+            0x606 => HCounterEn,
+            0x607 => HGEIE,
+            0x643 => HTVal,
+            0x644 => HIP,
+            0x645 => HVIP,
+            0x64A => HTInst,
+            0xE12 => HGEIP,
+            0x60A => HEnvCfg,
+            0x680 => HGATP,
+            0x6A8 => HContext,
+            0x605 => HTimeDelta,
+
+            0x14D => STimeCmp,
+            0x24D => VSTimeCmp,
+
+            0x350 => MISelect,
+            0x351 => MIReg,
+            0x35C => MTopEI,
+            0xFB0 => MTopI,
+            0x308 => MVIEn,
+            0x309 => MVIP,
+            0x150 => SISelect,
+            0x151 => SIReg,
+            0x15C => STopEI,
+            0xDB0 => STopI,
+            0x608 => HVIEn,
+            0x609 => HVICtl,
+            0x646 => HVIPrio1,
+            0x647 => HVIPrio2,
+            0x250 => VSISelect,
+            0x251 => VSIReg,
+            0x25C => VSTopEI,
+            0xEB0 => VSTopI,
+
+            0x200 => VSStatus,
+            0x204 => VSIE,
+            0x205 => VSTVec,
+            0x240 => VSScratch,
+            0x241 => VSEPC,
+            0x242 => VSCause,
+            0x243 => VSTVal,
+            0x244 => VSIP,
+            0x280 => VSATP,
+
             0x300 => MStatus,
             0x301 => MISA,
             0x302 => MEDeleg,
@@ -137,9 +471,33 @@ impl From<u64> for CsrRegister {
             0xF13 => MImpID,
             0xF14 => MHartID,
 
+            0x740 => MNScratch,
+            0x741 => MNEPC,
+            0x742 => MNCause,
+            0x744 => MNStatus,
+
+            0x7A0 => TSelect,
+            0x7A1 => TData1,
+            0x7A2 => TData2,
+            0x7A3 => TData3,
+            0x7A4 => TInfo,
+            0x7A5 => TControl,
+            0x7A8 => MContext,
+
+            0x7B0 => DCSR,
+            0x7B1 => DPC,
+            0x7B2 => DScratch0,
+            0x7B3 => DScratch1,
+
             0x3A0..=0x3AF => PMPCfg((num - 0x3A0) as u8),
             0x3B0..=0x3EF => PMPAddr((num - 0x3B0) as u8),
 
+            0xB00 => MCycle,
+            0xB02 => MInstRet,
+            0xB03..=0xB1F => MHPMCounter((num - 0xB00) as u8),
+            0x320 => MCountInhibit,
+            0x323..=0x33F => MHPMEvent((num - 0x320) as u8),
+
             _ => {
                 if num < 4096 {
                     Other(num as u16)
@@ -153,13 +511,77 @@ impl From<u64> for CsrRegister {
 
 impl Display for CsrRegister {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let _ = write!(f, "\x1B[35m");
-        let retval = match self {
-            Other(reg) => write!(f, "UNKNOWN(0x{:0X})", reg),
-            Invalid(reg) => write!(f, "INVALID(0x{:0X})", reg),
-            _ => write!(f, "{}", format!("{:?}", self).to_lowercase()),
-        };
-        let _ = write!(f, "\x1B[0m");
-        retval
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A register reached through an `*ireg` window, selected by the value in the
+/// matching `*iselect` CSR (see [`CsrRegister::indirect_select`]).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IndirectCsr {
+    /// Major interrupt priorities, iprio0 - iprio15 (0x30 - 0x3F)
+    IPrio(u8),
+    /// IMSIC interrupt delivery enable (0x70)
+    EIDelivery,
+    /// IMSIC interrupt enable threshold (0x72)
+    EIThreshold,
+    /// IMSIC interrupt-pending bits, eip0 - eip63 (0x80 - 0xBF)
+    EIP(u8),
+    /// IMSIC interrupt-enable bits, eie0 - eie63 (0xC0 - 0xFF)
+    EIE(u8),
+    /// Select values with the most-significant bit set are for custom use
+    Custom(u64),
+    Reserved(u64),
+}
+
+impl IndirectCsr {
+    /// Returns false for the odd-numbered registers, which only exist when XLEN is 32.
+    pub fn exists_in_rv64(&self) -> bool {
+        match self {
+            IndirectCsr::IPrio(num) | IndirectCsr::EIP(num) | IndirectCsr::EIE(num) => num & 1 == 0,
+            IndirectCsr::Reserved(_) => false,
+            _ => true,
+        }
+    }
+}
+
+impl From<u64> for IndirectCsr {
+    fn from(select: u64) -> Self {
+        match select {
+            0x30..=0x3F => IndirectCsr::IPrio((select - 0x30) as u8),
+            0x70 => IndirectCsr::EIDelivery,
+            0x72 => IndirectCsr::EIThreshold,
+            0x80..=0xBF => IndirectCsr::EIP((select - 0x80) as u8),
+            0xC0..=0xFF => IndirectCsr::EIE((select - 0xC0) as u8),
+            _ if select >> 63 == 1 => IndirectCsr::Custom(select),
+            _ => IndirectCsr::Reserved(select),
+        }
+    }
+}
+
+impl From<IndirectCsr> for u64 {
+    fn from(reg: IndirectCsr) -> Self {
+        match reg {
+            IndirectCsr::IPrio(num) => num as u64 + 0x30,
+            IndirectCsr::EIDelivery => 0x70,
+            IndirectCsr::EIThreshold => 0x72,
+            IndirectCsr::EIP(num) => num as u64 + 0x80,
+            IndirectCsr::EIE(num) => num as u64 + 0xC0,
+            IndirectCsr::Custom(select) | IndirectCsr::Reserved(select) => select,
+        }
+    }
+}
+
+impl Display for IndirectCsr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndirectCsr::IPrio(num) => write!(f, "iprio{}", num),
+            IndirectCsr::EIDelivery => write!(f, "eidelivery"),
+            IndirectCsr::EIThreshold => write!(f, "eithreshold"),
+            IndirectCsr::EIP(num) => write!(f, "eip{}", num),
+            IndirectCsr::EIE(num) => write!(f, "eie{}", num),
+            IndirectCsr::Custom(select) => write!(f, "CUSTOM(0x{:0X})", select),
+            IndirectCsr::Reserved(select) => write!(f, "RESERVED(0x{:0X})", select),
+        }
     }
 }
\ No newline at end of file
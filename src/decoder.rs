@@ -1,9 +1,13 @@
-use crate::instruction::{FenceFlags, Instruction};
+use crate::explain::fields;
+use crate::format::{FormatOptions, Token};
+use crate::instruction::{DecodedInstruction, FenceFlags, Instruction, InstructionFormat};
 use crate::optype::OpType;
-use crate::Register;
-use crate::compressed::decode_compressed;
+use crate::{instruction_length, opcode_size, Register, MAX_OPCODE_SIZE};
+use crate::compressed::{compressed_mnemonic, decode_compressed, decode_compressed_rv32};
+use crate::error::{DecodeError, DecodeErrorKind, Field};
+use crate::extension::Extension;
 
-fn decode_load(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_load(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_load(full_opcode) {
         OpType::Load { rd, rs1, funct3, imm } => {
             Ok(match funct3 {
@@ -14,36 +18,38 @@ fn decode_load(full_opcode: u32) -> Result<Instruction, String> {
                 0b101 => Instruction::Lhu { rd, rs1, imm, },
                 0b110 => Instruction::Lwu { rd, rs1, imm, },
                 0b011 => Instruction::Ld { rd, rs1, imm, },
-                _ => return Err(format!("Invalid LOAD funct3: 0b{funct3:03b}")),
+                _ => return Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32)),
             })
         }
         _ => unreachable!()
     }
 }
 
-fn decode_misc_mem(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_misc_mem(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_i(full_opcode) {
-        OpType::I { rd, rs1, funct3, imm } => {
-            if rd != Register::Zero || rs1 != Register::Zero {
-                return Err("MISC-MEM error".to_string());
-            }
+        // rd and rs1 are reserved for finer-grained fences, base implementations ignore them
+        OpType::I { funct3, .. } => {
             match funct3 {
                 0b000 => {
                     let succ = FenceFlags::from(((full_opcode >> 20) & 0b1111) as u8);
                     let pred = FenceFlags::from(((full_opcode >> 24) & 0b1111) as u8);
-                    Ok(Instruction::Fence { pred, succ })
+                    // Other fm values are reserved and execute as a normal fence
+                    match full_opcode >> 28 {
+                        0b1000 if u8::from(pred) == 0b0011 && u8::from(succ) == 0b0011 => Ok(Instruction::FenceTso),
+                        _ => Ok(Instruction::Fence { pred, succ }),
+                    }
                 }
                 0b001 => {
                     Ok(Instruction::FenceI)
                 }
-                _ => Err(format!("Invalid MISC-MEM funct3: 0b{funct3:03b}")),
+                _ => Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32)),
             }
         }
         _ => unreachable!(),
     }
 }
 
-fn decode_op_imm(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_op_imm(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_i(full_opcode) {
         OpType::I { rd, rs1, funct3, imm } => {
             Ok(match funct3 {
@@ -53,12 +59,17 @@ fn decode_op_imm(full_opcode: u32) -> Result<Instruction, String> {
                 0b100 => Instruction::Xori { rd, rs1, imm, },
                 0b110 => Instruction::Ori { rd, rs1, imm, },
                 0b111 => Instruction::Andi { rd, rs1, imm, },
-                0b001 => Instruction::Slli { rd, rs1, shamt: (imm & 0b11111) as u64 },
+                0b001 => {
+                    match (imm>>6)&0b111111 {
+                        0b000000 => Instruction::Slli { rd, rs1, shamt: (imm & 0b111111) as u64 },
+                        funct6 => return Err(DecodeError::illegal(full_opcode, Field::Funct6, funct6 as u32))
+                    }
+                },
                 0b101 => {
-                    match imm&0b11111000000 {
-                        0b00000000000 => Instruction::Srli { rd, rs1, shamt: (imm & 0b111111) as u64, },
-                        0b01000000000 => Instruction::Srai { rd, rs1, shamt: (imm & 0b111111) as u64, },
-                        _ => return Err(format!("Invalid immediate shift imm: 0b{imm:011b}"))
+                    match (imm>>6)&0b111111 {
+                        0b000000 => Instruction::Srli { rd, rs1, shamt: (imm & 0b111111) as u64, },
+                        0b010000 => Instruction::Srai { rd, rs1, shamt: (imm & 0b111111) as u64, },
+                        funct6 => return Err(DecodeError::illegal(full_opcode, Field::Funct6, funct6 as u32))
                     }
                 },
                 _ => unreachable!(),
@@ -68,37 +79,32 @@ fn decode_op_imm(full_opcode: u32) -> Result<Instruction, String> {
     }
 }
 
-fn decode_op_imm_32(full_opcode: u32) -> Result<Instruction, String> {
-    let opt_inst = decode_op_imm(full_opcode);
-    if opt_inst.is_ok() {
-        Ok(match opt_inst.unwrap() {
-            Instruction::Addi { rd, rs1, imm } => Instruction::Addiw { rd, rs1, imm, },
-            Instruction::Slli { rd, rs1, shamt } => {
-                if shamt&0b100000 != 0 {
-                    return Err("Reserved slliw shamt[5] == 1".to_string());
-                }
-                Instruction::Slliw { rd, rs1, shamt }
+fn decode_op_imm_32(full_opcode: u32) -> Result<Instruction, DecodeError> {
+    Ok(match decode_op_imm(full_opcode)? {
+        Instruction::Addi { rd, rs1, imm } => Instruction::Addiw { rd, rs1, imm, },
+        Instruction::Slli { rd, rs1, shamt } => {
+            if shamt&0b100000 != 0 {
+                return Err(DecodeError::reserved(full_opcode, Field::Shamt, shamt as u32));
             }
-            Instruction::Srli { rd, rs1, shamt } => {
-                if shamt&0b100000 != 0 {
-                    return Err("Reserved srliw shamt[5] == 1".to_string());
-                }
-                Instruction::Srliw { rd, rs1, shamt }
+            Instruction::Slliw { rd, rs1, shamt }
+        }
+        Instruction::Srli { rd, rs1, shamt } => {
+            if shamt&0b100000 != 0 {
+                return Err(DecodeError::reserved(full_opcode, Field::Shamt, shamt as u32));
             }
-            Instruction::Srai { rd, rs1, shamt } => {
-                if shamt&0b100000 != 0 {
-                    return Err("Reserved sraiw shamt[5] == 1".to_string());
-                }
-                Instruction::Sraiw { rd, rs1, shamt }
+            Instruction::Srliw { rd, rs1, shamt }
+        }
+        Instruction::Srai { rd, rs1, shamt } => {
+            if shamt&0b100000 != 0 {
+                return Err(DecodeError::reserved(full_opcode, Field::Shamt, shamt as u32));
             }
-            _ => return Err("Invalid OP-IMM-32".to_string()),
-        })
-    } else {
-        opt_inst
-    }
+            Instruction::Sraiw { rd, rs1, shamt }
+        }
+        _ => return Err(DecodeError::illegal(full_opcode, Field::Funct3, (full_opcode >> 12) & 0b111)),
+    })
 }
 
-fn decode_store(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_store(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_s(full_opcode) {
         OpType::S { rs1, rs2, funct3, imm } => {
             match funct3 {
@@ -106,14 +112,14 @@ fn decode_store(full_opcode: u32) -> Result<Instruction, String> {
                 0b001 => Ok(Instruction::Sh { rs1, rs2, imm, }),
                 0b010 => Ok(Instruction::Sw { rs1, rs2, imm, }),
                 0b011 => Ok(Instruction::Sd { rs1, rs2, imm, }),
-                _ => return Err(format!("Invalid STORE funct3: 0b{funct3:03b}"))
+                _ => Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32))
             }
         }
         _ => unreachable!()
     }
 }
 
-fn decode_amo(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_amo(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_r(full_opcode) {
         OpType::R { rd, rs1, rs2, funct3, funct7 } => {
             let funct5 = (funct7>>2)&0b11111;
@@ -125,17 +131,25 @@ fn decode_amo(full_opcode: u32) -> Result<Instruction, String> {
                     match funct5 {
                         0b00000 => Ok(Instruction::Amoaddw { rd, rs1, rs2, aq, rl, }),
                         0b00001 => Ok(Instruction::Amoswapw { rd, rs1, rs2, aq, rl, }),
-                        _ => Err(format!("Invalid AMO funct5: 0b{funct5:05b}"))
+                        0b00100 => Ok(Instruction::Amoxorw { rd, rs1, rs2, aq, rl, }),
+                        0b01100 => Ok(Instruction::Amoandw { rd, rs1, rs2, aq, rl, }),
+                        0b01000 => Ok(Instruction::Amoorw { rd, rs1, rs2, aq, rl, }),
+                        0b10000 => Ok(Instruction::Amominw { rd, rs1, rs2, aq, rl, }),
+                        0b10100 => Ok(Instruction::Amomaxw { rd, rs1, rs2, aq, rl, }),
+                        0b11000 => Ok(Instruction::Amominuw { rd, rs1, rs2, aq, rl, }),
+                        0b11100 => Ok(Instruction::Amomaxuw { rd, rs1, rs2, aq, rl, }),
+                        _ => Err(DecodeError::unimplemented(full_opcode))
                     }
                 }
-                _ => Err(format!("Invalid AMO funct3: 0b{funct3:03b}"))
+                0b011 => Err(DecodeError::unimplemented(full_opcode)),
+                _ => Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32))
             }
         }
         _ => unreachable!(),
     }
 }
 
-fn decode_op(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_op(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_r(full_opcode) {
         OpType::R { rd, rs1, rs2, funct3, funct7 } => {
             Ok(match funct7 {
@@ -157,7 +171,7 @@ fn decode_op(full_opcode: u32) -> Result<Instruction, String> {
                     match funct3 {
                         0b000 => Instruction::Sub { rd, rs1, rs2, },
                         0b101 => Instruction::Sra { rd, rs1, rs2, },
-                        _ => return Err(format!("Invalid OP funct3 and funct7 combination: 0b{funct3:03b} and 0b{funct7:07b}")),
+                        _ => return Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32)),
                     }
                 },
                 // Math ISA
@@ -174,44 +188,39 @@ fn decode_op(full_opcode: u32) -> Result<Instruction, String> {
                         _ => unreachable!(),
                     }
                 }
-                _ => return Err(format!("Invalid OP funct7: 0b{funct7:07b}")),
+                _ => return Err(DecodeError::illegal(full_opcode, Field::Funct7, funct7 as u32)),
             })
         }
         _ => unreachable!(),
     }
 }
 
-fn decode_lui(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_lui(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_lui(full_opcode) {
         OpType::Lui { rd, imm } => Ok(Instruction::Lui { rd, uimm: imm }),
         _ => unreachable!()
     }
 }
 
-fn decode_op_32(full_opcode: u32) -> Result<Instruction, String> {
-    let opt_inst = decode_op(full_opcode);
-    if opt_inst.is_ok() {
-        Ok(match opt_inst.unwrap() {
-            Instruction::Add { rd, rs1, rs2 } => Instruction::Addw { rd, rs1, rs2 },
-            Instruction::Sub { rd, rs1, rs2 } => Instruction::Subw { rd, rs1, rs2 },
-            Instruction::Sll { rd, rs1, rs2 } => Instruction::Sllw { rd, rs1, rs2 },
-            Instruction::Srl { rd, rs1, rs2 } => Instruction::Srlw { rd, rs1, rs2 },
-            Instruction::Sra { rd, rs1, rs2 } => Instruction::Sraw { rd, rs1, rs2 },
-
-            Instruction::Mul { rd, rs1, rs2 } => Instruction::Mulw { rd, rs1, rs2 },
-            Instruction::Div { rd, rs1, rs2 } => Instruction::Divw { rd, rs1, rs2 },
-            Instruction::Divu { rd, rs1, rs2 } => Instruction::Divuw { rd, rs1, rs2 },
-            Instruction::Rem { rd, rs1, rs2 } => Instruction::Remw { rd, rs1, rs2 },
-            Instruction::Remu { rd, rs1, rs2 } => Instruction::Remuw { rd, rs1, rs2 },
-
-            _ => return Err("Invalid OP-32".to_string()),
-        })
-    } else {
-        opt_inst
-    }
+fn decode_op_32(full_opcode: u32) -> Result<Instruction, DecodeError> {
+    Ok(match decode_op(full_opcode)? {
+        Instruction::Add { rd, rs1, rs2 } => Instruction::Addw { rd, rs1, rs2 },
+        Instruction::Sub { rd, rs1, rs2 } => Instruction::Subw { rd, rs1, rs2 },
+        Instruction::Sll { rd, rs1, rs2 } => Instruction::Sllw { rd, rs1, rs2 },
+        Instruction::Srl { rd, rs1, rs2 } => Instruction::Srlw { rd, rs1, rs2 },
+        Instruction::Sra { rd, rs1, rs2 } => Instruction::Sraw { rd, rs1, rs2 },
+
+        Instruction::Mul { rd, rs1, rs2 } => Instruction::Mulw { rd, rs1, rs2 },
+        Instruction::Div { rd, rs1, rs2 } => Instruction::Divw { rd, rs1, rs2 },
+        Instruction::Divu { rd, rs1, rs2 } => Instruction::Divuw { rd, rs1, rs2 },
+        Instruction::Rem { rd, rs1, rs2 } => Instruction::Remw { rd, rs1, rs2 },
+        Instruction::Remu { rd, rs1, rs2 } => Instruction::Remuw { rd, rs1, rs2 },
+
+        _ => return Err(DecodeError::illegal(full_opcode, Field::Funct3, (full_opcode >> 12) & 0b111)),
+    })
 }
 
-fn decode_branch(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_branch(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_b(full_opcode) {
         OpType::B { rs1, rs2, funct3, imm } => {
             Ok(match funct3 {
@@ -221,45 +230,66 @@ fn decode_branch(full_opcode: u32) -> Result<Instruction, String> {
                 0b101 => Instruction::Bge { rs1, rs2, imm, },
                 0b110 => Instruction::Bltu { rs1, rs2, imm, },
                 0b111 => Instruction::Bgeu { rs1, rs2, imm, },
-                _ => return Err(format!("Invalid branch funct3: 0b{funct3:03b}")),
+                _ => return Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32)),
             })
         }
         _ => unreachable!(),
     }
 }
 
-fn decode_jalr(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_jalr(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_jalr(full_opcode) {
         OpType::Jalr { rd, rs1, funct3, imm } => {
             Ok(match funct3 {
                 0b000 => Instruction::Jalr { rd, rs1, imm, },
-                _ => return Err(format!("Invalid jalr funct3: 0b{funct3:03b}")),
+                _ => return Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32)),
             })
         }
         _ => unreachable!()
     }
 }
 
-fn decode_jal(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_jal(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_jal(full_opcode) {
         OpType::Jal { rd, imm } => Ok(Instruction::Jal { rd, imm }),
         _ => unreachable!()
     }
 }
 
-fn decode_system(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_system(full_opcode: u32) -> Result<Instruction, DecodeError> {
     match OpType::new_csr(full_opcode) {
         OpType::Csr { rd, rs1, funct3, csr } => {
             // Remove sign extension
             Ok(match funct3 {
                 0b000 => {
-                    if rd != Register::Zero || rs1 != Register::Zero {
-                        return Err("Invalid rd and rs1 for ECALL/EBREAK".to_string())
+                    if rd != Register::Zero {
+                        return Err(DecodeError::illegal(full_opcode, Field::Rd, rd as u32));
+                    }
+                    let rs2 = (full_opcode >> 20) & 0b11111;
+                    match full_opcode >> 25 {
+                        // sfence.vma, sinval.vma, hfence.vvma, hinval.vvma, hfence.gvma and hinval.gvma
+                        0b0001001 | 0b0001011 | 0b0010001 | 0b0010011 | 0b0110001 | 0b0110011 => {
+                            return Err(DecodeError::unimplemented(full_opcode));
+                        }
+                        // sfence.w.inval and sfence.inval.ir
+                        0b0001100 if rs1 == Register::Zero && rs2 <= 1 => {
+                            return Err(DecodeError::unimplemented(full_opcode));
+                        }
+                        _ => {}
+                    }
+                    if rs1 != Register::Zero {
+                        return Err(DecodeError::illegal(full_opcode, Field::Rs1, rs1 as u32));
                     }
                     match usize::from(csr) {
-                        0 => Instruction::Ecall,
-                        1 => Instruction::Ebreak,
-                        _ => return Err("Invalid immediate for ECALL/EBREAK".to_string()),
+                        0x000 => Instruction::Ecall,
+                        0x001 => Instruction::Ebreak,
+                        0x002 => Instruction::Uret,
+                        0x102 => Instruction::Sret,
+                        0x105 => Instruction::Wfi,
+                        0x302 => Instruction::Mret,
+                        0x702 => Instruction::Mnret,
+                        0x7B2 => Instruction::Dret,
+                        funct12 => return Err(DecodeError::illegal(full_opcode, Field::Funct12, funct12 as u32)),
                     }
                 }
                 0b001 => Instruction::Csrrw { rd, rs1, csr, },
@@ -268,22 +298,80 @@ fn decode_system(full_opcode: u32) -> Result<Instruction, String> {
                 0b101 => Instruction::Csrrwi { rd, imm: rs1 as usize as i64, csr, },
                 0b110 => Instruction::Csrrsi { rd, imm: rs1 as usize as i64, csr, },
                 0b111 => Instruction::Csrrci { rd, imm: rs1 as usize as i64, csr, },
-                _ => return Err(format!("Invalid system funct3: 0b{funct3:03b}")),
+                _ => return Err(DecodeError::illegal(full_opcode, Field::Funct3, funct3 as u32)),
             })
         }
         _ => unreachable!()
     }
 }
 
-pub fn decode(full_opcode: u32) -> Result<Instruction, String> {
+fn decode_long(full_opcode: u32) -> Result<Instruction, DecodeError> {
+    // These don't fit in 32 bits, decode_bytes has to be used instead
+    match instruction_length(full_opcode as u16) {
+        Some(_) => Err(DecodeError::truncated(full_opcode)),
+        None => Err(DecodeError::reserved_length(full_opcode)),
+    }
+}
+
+/// Decodes the instruction at the start of `bytes`, which may be of any length the
+/// RISC-V length encoding allows. Use [`crate::opcode_size`] to find out how far to advance.
+pub fn decode_bytes(bytes: &[u8]) -> Result<Instruction, DecodeError> {
+    if bytes.len() < 2 {
+        return Err(DecodeError::truncated(bytes.first().copied().unwrap_or(0b11) as u32));
+    }
+    let parcel = u16::from_le_bytes([bytes[0], bytes[1]]);
+    let mut raw = [0u8; 4];
+    raw[..bytes.len().min(4)].copy_from_slice(&bytes[..bytes.len().min(4)]);
+    let len = instruction_length(parcel)
+        .ok_or_else(|| DecodeError::reserved_length(u32::from_le_bytes(raw)))?;
+    if bytes.len() < len {
+        return Err(DecodeError::truncated(u32::from_le_bytes(raw)));
+    }
+    match len {
+        2 => decode_compressed(parcel),
+        4 => decode(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        _ => {
+            let mut raw = [0u8; MAX_OPCODE_SIZE];
+            raw[..len].copy_from_slice(&bytes[..len]);
+            Ok(Instruction::Long { len, raw })
+        }
+    }
+}
+
+fn describe(full_opcode: u32, instruction: Instruction) -> DecodedInstruction {
+    let len = opcode_size(full_opcode);
+    let compressed = if len == 2 {
+        Some(compressed_mnemonic(full_opcode as u16, &instruction))
+    } else {
+        None
+    };
+    DecodedInstruction { instruction, raw: full_opcode, len, compressed }
+}
+
+/// Like [`decode`], but keeps the raw bits, length and compressed mnemonic.
+pub fn decode_full(full_opcode: u32) -> Result<DecodedInstruction, DecodeError> {
+    let full_opcode = if instruction_length(full_opcode as u16) == Some(2) { full_opcode & 0xFFFF } else { full_opcode };
+    Ok(describe(full_opcode, decode(full_opcode)?))
+}
+
+/// Like [`decode_bytes`], but keeps the raw bits, length and compressed mnemonic.
+pub fn decode_full_bytes(bytes: &[u8]) -> Result<DecodedInstruction, DecodeError> {
+    let instruction = decode_bytes(bytes)?;
+    let mut raw = [0u8; 4];
+    let len = opcode_size(u16::from_le_bytes([bytes[0], bytes[1]]) as u32).min(4);
+    raw[..len].copy_from_slice(&bytes[..len]);
+    Ok(describe(u32::from_le_bytes(raw), instruction))
+}
+
+pub fn decode(full_opcode: u32) -> Result<Instruction, DecodeError> {
     let opcode = full_opcode&0x7F;
     match opcode&0b11 {
         0b11 => {
             // RV64G
             match (opcode&0b1111100)>>2 {
                 0b00000 => decode_load(full_opcode),
-                0b00001 => Err("TODO: Implement LOAD-FP".to_string()),
-                0b00010 => Err("TODO: Implement custom-0".to_string()),
+                0b00001 => Err(DecodeError::unimplemented(full_opcode)),
+                0b00010 => Err(DecodeError::illegal(full_opcode, Field::Opcode, opcode)),
                 0b00011 => decode_misc_mem(full_opcode),
                 0b00100 => decode_op_imm(full_opcode),
                 0b00101 => {
@@ -293,31 +381,31 @@ pub fn decode(full_opcode: u32) -> Result<Instruction, String> {
                     }
                 },
                 0b00110 => decode_op_imm_32(full_opcode),
-                0b00111 => Err("TODO: Implement uhhhhhhhhhhhhhhh 48b".to_string()),
+                0b00111 => decode_long(full_opcode),
                 0b01000 => decode_store(full_opcode),
-                0b01001 => Err("TODO: Implement STORE-FP".to_string()),
-                0b01010 => Err("TODO: Implement custom-1".to_string()),
+                0b01001 => Err(DecodeError::unimplemented(full_opcode)),
+                0b01010 => Err(DecodeError::illegal(full_opcode, Field::Opcode, opcode)),
                 0b01011 => decode_amo(full_opcode),
                 0b01100 => decode_op(full_opcode),
                 0b01101 => decode_lui(full_opcode),
                 0b01110 => decode_op_32(full_opcode),
-                0b01111 => Err("TODO: Implement uhhhhhhhhhhhhhhh 64b".to_string()),
-                0b10000 => Err("TODO: Implement MADD".to_string()),
-                0b10001 => Err("TODO: Implement MSUB".to_string()),
-                0b10010 => Err("TODO: Implement NMSUB".to_string()),
-                0b10011 => Err("TODO: Implement NMADD".to_string()),
-                0b10100 => Err("TODO: Implement OP-FP".to_string()),
-                0b10101 => Err("TODO: Implement reserved".to_string()),
-                0b10110 => Err("TODO: Implement custom-2".to_string()),
-                0b10111 => Err("TODO: Implement uhhhhhhhhhhhhhhh 48b".to_string()),
+                0b01111 => decode_long(full_opcode),
+                0b10000 => Err(DecodeError::unimplemented(full_opcode)),
+                0b10001 => Err(DecodeError::unimplemented(full_opcode)),
+                0b10010 => Err(DecodeError::unimplemented(full_opcode)),
+                0b10011 => Err(DecodeError::unimplemented(full_opcode)),
+                0b10100 => Err(DecodeError::unimplemented(full_opcode)),
+                0b10101 => Err(DecodeError::unimplemented(full_opcode)),
+                0b10110 => Err(DecodeError::illegal(full_opcode, Field::Opcode, opcode)),
+                0b10111 => decode_long(full_opcode),
                 0b11000 => decode_branch(full_opcode),
                 0b11001 => decode_jalr(full_opcode),
-                0b11010 => Err("TODO: Implement reserved".to_string()),
+                0b11010 => Err(DecodeError::reserved(full_opcode, Field::Opcode, opcode)),
                 0b11011 => decode_jal(full_opcode),
                 0b11100 => decode_system(full_opcode),
-                0b11101 => Err("TODO: Implement reserved".to_string()),
-                0b11110 => Err("TODO: Implement custom-3".to_string()),
-                0b11111 => Err("TODO: Implement uhhhhhhhhhhhhhhh >=80b".to_string()),
+                0b11101 => Err(DecodeError::reserved(full_opcode, Field::Opcode, opcode)),
+                0b11110 => Err(DecodeError::illegal(full_opcode, Field::Opcode, opcode)),
+                0b11111 => decode_long(full_opcode),
                 _ => unreachable!(),
             }
         }
@@ -326,3 +414,207 @@ pub fn decode(full_opcode: u32) -> Result<Instruction, String> {
         }
     }
 }
+
+/// Decoder for a specific core configuration. Instructions from disabled extensions
+/// are rejected with [`DecodeErrorKind::ExtensionDisabled`], RV64-only
+/// instructions are illegal when XLEN is 32, and x16-x31 are illegal on the E base.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Decoder {
+    xlen: usize,
+    extensions: u64,
+    embedded: bool,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    /// RV64 with every extension enabled, this is what [`decode`] does.
+    pub fn new() -> Self {
+        let mut decoder = Self { xlen: 64, extensions: 0, embedded: false };
+        Extension::ALL.iter().for_each(|ext| decoder.enable(*ext));
+        decoder
+    }
+
+    /// Parses an ISA string such as `rv64imafdc_zicsr_zba_zbb`. Version numbers are
+    /// accepted and ignored, unknown extensions are ignored. `g` expands to
+    /// `imafd_zicsr_zifencei` and an `e` base limits registers to x0-x15. Privilege modes aren't part of ISA strings, so S
+    /// is enabled and can be turned off with [`Decoder::disable`].
+    pub fn from_isa_string(isa: &str) -> Result<Self, String> {
+        let isa = isa.trim().to_lowercase();
+        let (xlen, rest) = if let Some(rest) = isa.strip_prefix("rv64") {
+            (64, rest)
+        } else if let Some(rest) = isa.strip_prefix("rv32") {
+            (32, rest)
+        } else {
+            return Err(format!("ISA string must start with rv32 or rv64: {isa}"));
+        };
+        let mut decoder = Self { xlen, extensions: 0, embedded: false };
+        decoder.enable(Extension::S);
+
+        let mut parts = rest.split('_').filter(|part| !part.is_empty());
+        let single = parts.next().unwrap_or("");
+        if !single.starts_with(['i', 'e', 'g']) {
+            return Err(format!("ISA string must start with a base ISA (i, e or g): {isa}"));
+        }
+        decoder.embedded = single.starts_with('e');
+        let mut letters = single.chars().peekable();
+        while let Some(letter) = letters.next() {
+            // Skip version numbers like 2p1
+            while letters.next_if(|c| c.is_ascii_digit() || *c == 'p').is_some() {}
+            match letter {
+                'g' => {
+                    for ext in [Extension::I, Extension::M, Extension::A, Extension::F, Extension::D, Extension::Zicsr, Extension::Zifencei] {
+                        decoder.enable(ext);
+                    }
+                }
+                'b' => {
+                    for ext in [Extension::Zba, Extension::Zbb, Extension::Zbs] {
+                        decoder.enable(ext);
+                    }
+                }
+                _ => if let Some(ext) = Extension::from_letter(letter) {
+                    decoder.enable(ext);
+                }
+            }
+        }
+        for part in parts {
+            let name = part.trim_end_matches(|c: char| c.is_ascii_digit() || c == 'p');
+            if name.starts_with('x') {
+                decoder.enable(Extension::Custom);
+            } else if let Some(ext) = Extension::ALL.iter().find(|ext| ext.to_string() == name) {
+                decoder.enable(*ext);
+            }
+        }
+        Ok(decoder)
+    }
+
+    /// Builds a decoder from the value of the `misa` CSR. MXL is read from bits
+    /// 63:62, or from bits 31:30 when those are zero, as for an RV32 `misa` read
+    /// into a `u64`. Zicsr and Zifencei have no `misa` bit and are always enabled.
+    pub fn from_misa(misa: u64) -> Self {
+        let mxl = match misa >> 62 {
+            0 => misa >> 30 & 0b11,
+            mxl => mxl,
+        };
+        let xlen = match mxl {
+            1 => 32,
+            _ => 64,
+        };
+        // E reads as the complement of I, set means the 16-register base
+        let mut decoder = Self { xlen, extensions: 0, embedded: (misa >> 4) & 1 == 1 };
+        decoder.enable(Extension::Zicsr);
+        decoder.enable(Extension::Zifencei);
+        for bit in 0..26 {
+            if (misa >> bit) & 1 == 1 {
+                match (b'a' + bit) as char {
+                    'b' => {
+                        for ext in [Extension::Zba, Extension::Zbb, Extension::Zbs] {
+                            decoder.enable(ext);
+                        }
+                    }
+                    letter => if let Some(ext) = Extension::from_letter(letter) {
+                        decoder.enable(ext);
+                    }
+                }
+            }
+        }
+        decoder
+    }
+
+    pub fn xlen(&self) -> usize {
+        self.xlen
+    }
+
+    /// True for the RV32E/RV64E base, which only has x0-x15.
+    pub fn is_embedded(&self) -> bool {
+        self.embedded
+    }
+
+    /// Enables `ext` and everything it depends on.
+    pub fn enable(&mut self, ext: Extension) {
+        self.extensions |= 1 << ext as u8;
+        ext.implies().iter().for_each(|ext| self.extensions |= 1 << *ext as u8);
+    }
+
+    pub fn disable(&mut self, ext: Extension) {
+        self.extensions &= !(1 << ext as u8);
+    }
+
+    pub fn is_enabled(&self, ext: Extension) -> bool {
+        (self.extensions >> ext as u8) & 1 == 1
+    }
+
+    pub fn decode(&self, full_opcode: u32) -> Result<Instruction, DecodeError> {
+        self.check(full_opcode, self.decode_xlen(full_opcode)?)
+    }
+
+    pub fn decode_bytes(&self, bytes: &[u8]) -> Result<Instruction, DecodeError> {
+        self.decode_full_bytes(bytes).map(|decoded| decoded.instruction)
+    }
+
+    pub fn decode_full(&self, full_opcode: u32) -> Result<DecodedInstruction, DecodeError> {
+        let full_opcode = if instruction_length(full_opcode as u16) == Some(2) { full_opcode & 0xFFFF } else { full_opcode };
+        let instruction = self.check(full_opcode, self.decode_xlen(full_opcode)?)?;
+        Ok(describe(full_opcode, instruction))
+    }
+
+    pub fn decode_full_bytes(&self, bytes: &[u8]) -> Result<DecodedInstruction, DecodeError> {
+        if let [lo, hi, ..] = *bytes {
+            if self.xlen == 32 && instruction_length(u16::from_le_bytes([lo, hi])) == Some(2) {
+                return self.decode_full(u16::from_le_bytes([lo, hi]) as u32);
+            }
+        }
+        let decoded = decode_full_bytes(bytes)?;
+        self.check(decoded.raw, decoded.instruction)?;
+        Ok(decoded)
+    }
+
+    /// RV32 gives some RVC encodings a different meaning, everything else decodes the same.
+    fn decode_xlen(&self, full_opcode: u32) -> Result<Instruction, DecodeError> {
+        match (self.xlen, instruction_length(full_opcode as u16)) {
+            (32, Some(2)) => decode_compressed_rv32(full_opcode as u16),
+            _ => decode(full_opcode),
+        }
+    }
+
+    fn check(&self, full_opcode: u32, inst: Instruction) -> Result<Instruction, DecodeError> {
+        let compressed = opcode_size(full_opcode) == 2;
+        if compressed && !self.is_enabled(Extension::C) {
+            return Err(DecodeError::new(DecodeErrorKind::ExtensionDisabled(Extension::C), full_opcode, None));
+        }
+        if !self.is_enabled(inst.extension()) {
+            return Err(DecodeError::new(DecodeErrorKind::ExtensionDisabled(inst.extension()), full_opcode, None));
+        }
+        if self.xlen == 32 && inst.is_rv64_only() {
+            return Err(match inst {
+                Instruction::Slli { shamt, .. } | Instruction::Srli { shamt, .. } | Instruction::Srai { shamt, .. } => {
+                    DecodeError::illegal(full_opcode, Field::Shamt, shamt as u32)
+                }
+                _ => DecodeError::illegal(full_opcode, Field::Opcode, full_opcode & 0x7F),
+            });
+        }
+        if self.embedded {
+            if let Some((field, reg)) = upper_register(full_opcode, &inst) {
+                return Err(DecodeError::illegal(full_opcode, field, reg));
+            }
+        }
+        Ok(inst)
+    }
+}
+
+/// The first register operand of `inst` above x15 and the field that holds it.
+fn upper_register(full_opcode: u32, inst: &Instruction) -> Option<(Field, u32)> {
+    let reg = inst.tokens_with(&FormatOptions::default().without_aliases()).into_iter().find_map(|token| match token {
+        Token::Register(reg) | Token::Memory { base: reg, .. } => Some(usize::from(reg) as u32).filter(|reg| *reg >= 16),
+        _ => None,
+    })?;
+    let fields = fields(full_opcode, InstructionFormat::of(full_opcode));
+    let field = [Field::Rd, Field::Rs1, Field::Rs2]
+        .into_iter()
+        .find(|field| fields.iter().any(|bits| bits.field == Some(*field) && bits.register().is_some() && bits.value == reg));
+    Some((field.unwrap_or(Field::Rd), reg))
+}
@@ -0,0 +1,257 @@
+use crate::decoder::Decoder;
+use crate::error::ElfError;
+use crate::listing::Listing;
+
+const EM_RISCV: u16 = 243;
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHT_RISCV_ATTRIBUTES: u32 = 0x7000_0003;
+const SHF_EXECINSTR: u64 = 0x4;
+/// `e_flags` bit set when the file contains compressed instructions.
+const EF_RISCV_RVC: u32 = 0x1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+const TAG_RISCV_ARCH: u64 = 5;
+
+/// A section from the section header table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section<'a> {
+    pub name: String,
+    /// `sh_type`, e.g. 1 for SHT_PROGBITS.
+    pub kind: u32,
+    pub flags: u64,
+    pub address: u64,
+    /// Contents of the section, empty for SHT_NOBITS.
+    pub data: &'a [u8],
+}
+
+impl Section<'_> {
+    pub fn is_executable(&self) -> bool {
+        self.flags & SHF_EXECINSTR != 0
+    }
+}
+
+/// A named entry of `.symtab`. Section and file symbols are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: u64,
+    pub size: u64,
+    /// `STT_*` from `st_info`, e.g. 2 for STT_FUNC.
+    pub kind: u8,
+}
+
+/// A little-endian RISC-V ELF file, parsed far enough to disassemble it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Elf<'a> {
+    /// 32 or 64, from `EI_CLASS`.
+    pub xlen: usize,
+    pub entry: u64,
+    pub flags: u32,
+    pub sections: Vec<Section<'a>>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// Bounds-checked little-endian reads, `word` is 4 or 8 bytes depending on the class.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    xlen: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&self, offset: u64, len: u64) -> Result<&'a [u8], ElfError> {
+        let end = offset.checked_add(len).ok_or(ElfError::Truncated)?;
+        self.bytes.get(offset as usize..end as usize).ok_or(ElfError::Truncated)
+    }
+
+    fn u8(&self, offset: u64) -> Result<u8, ElfError> {
+        Ok(self.slice(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, ElfError> {
+        Ok(u16::from_le_bytes(self.slice(offset, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, ElfError> {
+        Ok(u32::from_le_bytes(self.slice(offset, 4)?.try_into().unwrap()))
+    }
+
+    fn word(&self, offset: u64) -> Result<u64, ElfError> {
+        match self.xlen {
+            32 => Ok(self.u32(offset)? as u64),
+            _ => Ok(u64::from_le_bytes(self.slice(offset, 8)?.try_into().unwrap())),
+        }
+    }
+}
+
+/// Reads the NUL-terminated string at `offset` in a string table.
+fn c_str(table: &[u8], offset: usize) -> String {
+    let bytes = table.get(offset..).unwrap_or(&[]);
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn uleb128(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Finds `Tag_RISCV_arch` in the contents of a `.riscv.attributes` section:
+/// a format version `A`, then subsections `len:u32 vendor\0 tag:uleb len:u32 attributes...`.
+fn attributes_arch(data: &[u8]) -> Option<String> {
+    let mut pos = 1;
+    if data.first() != Some(&b'A') {
+        return None;
+    }
+    while pos + 4 <= data.len() {
+        let len = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let subsection = data.get(pos + 4..pos + len)?;
+        pos += len.max(4);
+        let vendor = c_str(subsection, 0);
+        if vendor != "riscv" {
+            continue;
+        }
+        // Only file attributes (tag 1) matter, they are the first sub-subsection
+        let mut at = vendor.len() + 1;
+        if uleb128(subsection, &mut at)? != 1 {
+            continue;
+        }
+        at += 4;
+        while at < subsection.len() {
+            let tag = uleb128(subsection, &mut at)?;
+            // Odd tags are strings, even tags are numbers
+            if tag % 2 == 1 {
+                let value = c_str(subsection, at);
+                at += value.len() + 1;
+                if tag == TAG_RISCV_ARCH {
+                    return Some(value);
+                }
+            } else {
+                uleb128(subsection, &mut at)?;
+            }
+        }
+    }
+    None
+}
+
+impl<'a> Elf<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, ElfError> {
+        if !bytes.starts_with(b"\x7fELF") {
+            return Err(ElfError::NotElf);
+        }
+        let mut reader = Reader { bytes, xlen: 64 };
+        reader.xlen = match reader.u8(4)? {
+            1 => 32,
+            2 => 64,
+            class => return Err(ElfError::InvalidClass(class)),
+        };
+        if reader.u8(5)? != 1 {
+            return Err(ElfError::BigEndian);
+        }
+        let machine = reader.u16(18)?;
+        if machine != EM_RISCV {
+            return Err(ElfError::UnsupportedMachine(machine));
+        }
+        // Offsets past e_entry depend on the word size
+        let w = reader.xlen as u64 / 8;
+        let entry = reader.word(24)?;
+        let shoff = reader.word(24 + 2 * w)?;
+        let flags = reader.u32(24 + 3 * w)?;
+        let shentsize = reader.u16(24 + 3 * w + 10)? as u64;
+        let shnum = reader.u16(24 + 3 * w + 12)? as u64;
+        let shstrndx = reader.u16(24 + 3 * w + 14)? as u64;
+
+        struct Header { name: u32, kind: u32, flags: u64, address: u64, offset: u64, size: u64, link: u32 }
+        let headers = (0..shnum).map(|i| {
+            let at = shoff + i * shentsize;
+            Ok(Header {
+                name: reader.u32(at)?,
+                kind: reader.u32(at + 4)?,
+                flags: reader.word(at + 8)?,
+                address: reader.word(at + 8 + w)?,
+                offset: reader.word(at + 8 + 2 * w)?,
+                size: reader.word(at + 8 + 3 * w)?,
+                link: reader.u32(at + 8 + 4 * w)?,
+            })
+        }).collect::<Result<Vec<_>, ElfError>>()?;
+        let data = |header: &Header| match header.kind {
+            SHT_NOBITS => Ok(&bytes[..0]),
+            _ => reader.slice(header.offset, header.size),
+        };
+
+        let names = match headers.get(shstrndx as usize) {
+            Some(header) => data(header)?,
+            None => &[],
+        };
+        let sections = headers.iter().map(|header| Ok(Section {
+            name: c_str(names, header.name as usize),
+            kind: header.kind,
+            flags: header.flags,
+            address: header.address,
+            data: data(header)?,
+        })).collect::<Result<Vec<_>, ElfError>>()?;
+
+        let mut symbols = Vec::new();
+        for header in headers.iter().filter(|header| header.kind == SHT_SYMTAB) {
+            let table = data(header)?;
+            let strings = headers.get(header.link as usize).map(data).transpose()?.unwrap_or(&[]);
+            let entsize = if reader.xlen == 32 { 16 } else { 24 };
+            for entry in table.chunks_exact(entsize) {
+                let entry = Reader { bytes: entry, xlen: reader.xlen };
+                let (info, value, size) = match reader.xlen {
+                    32 => (entry.u8(12)?, entry.word(4)?, entry.word(8)?),
+                    _ => (entry.u8(4)?, entry.word(8)?, entry.word(16)?),
+                };
+                let name = c_str(strings, entry.u32(0)? as usize);
+                let kind = info & 0xF;
+                // `$x` and `$d` are mapping symbols marking code and data
+                if name.is_empty() || name.starts_with('$') || kind == STT_SECTION || kind == STT_FILE {
+                    continue;
+                }
+                symbols.push(Symbol { name, value, size, kind });
+            }
+        }
+        Ok(Self { xlen: reader.xlen, entry, flags, sections, symbols })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section<'a>> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// The ISA string from `Tag_RISCV_arch` in `.riscv.attributes`, e.g. `rv64i2p1_m2p0_c2p0`.
+    pub fn arch(&self) -> Option<String> {
+        self.sections
+            .iter()
+            .filter(|section| section.kind == SHT_RISCV_ATTRIBUTES)
+            .find_map(|section| attributes_arch(section.data))
+    }
+
+    /// A decoder for the extensions the file was built for. Without an arch
+    /// attribute this is RV32G or RV64G, plus C if `e_flags` says so.
+    pub fn decoder(&self) -> Decoder {
+        let fallback = format!("rv{}g{}", self.xlen, if self.flags & EF_RISCV_RVC != 0 { "c" } else { "" });
+        self.arch()
+            .and_then(|arch| Decoder::from_isa_string(&arch).ok())
+            .filter(|decoder| decoder.xlen() == self.xlen)
+            .unwrap_or_else(|| Decoder::from_isa_string(&fallback).unwrap())
+    }
+
+    /// Lists `section` with [`Elf::decoder`], naming addresses after the symbol table.
+    pub fn listing(&self, section: &Section<'a>) -> Listing<'a> {
+        // Functions first, so they win over other symbols at the same address
+        let (functions, others): (Vec<&Symbol>, Vec<&Symbol>) = self.symbols.iter().partition(|symbol| symbol.kind == STT_FUNC);
+        let symbols = functions.into_iter().chain(others);
+        Listing::new(section.data, section.address)
+            .with_decoder(self.decoder())
+            .with_symbols(symbols.map(|symbol| (symbol.value, symbol.name.clone())))
+    }
+}
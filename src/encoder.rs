@@ -0,0 +1,210 @@
+use crate::csr::CsrRegister;
+use crate::error::EncodeError;
+use crate::instruction::Instruction;
+use crate::instruction::Instruction::*;
+use crate::register::Register;
+
+const LOAD: u32 = 0b0000011;
+const MISC_MEM: u32 = 0b0001111;
+const OP_IMM: u32 = 0b0010011;
+const AUIPC: u32 = 0b0010111;
+const OP_IMM_32: u32 = 0b0011011;
+const STORE: u32 = 0b0100011;
+const AMO: u32 = 0b0101111;
+const OP: u32 = 0b0110011;
+const LUI: u32 = 0b0110111;
+const OP_32: u32 = 0b0111011;
+const BRANCH: u32 = 0b1100011;
+const JALR: u32 = 0b1100111;
+const JAL: u32 = 0b1101111;
+const SYSTEM: u32 = 0b1110011;
+
+/// Checks that `imm` fits in a `bits` wide field and is a multiple of `align`,
+/// and returns it truncated to `bits`.
+pub(crate) fn check_imm(imm: i64, bits: u32, align: i64, signed: bool) -> Result<u32, EncodeError> {
+    let (min, max) = if signed {
+        (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+    } else {
+        (0, (1 << bits) - 1)
+    };
+    if imm < min || imm > max {
+        return Err(EncodeError::ImmOutOfRange { imm, min, max });
+    }
+    if imm % align != 0 {
+        return Err(EncodeError::ImmMisaligned { imm, align });
+    }
+    Ok((imm as u32) & ((1 << bits) - 1) as u32)
+}
+
+fn csr_address(csr: CsrRegister) -> Result<u32, EncodeError> {
+    match usize::from(csr) {
+        addr @ 0..=0xFFF => Ok(addr as u32),
+        _ => Err(EncodeError::InvalidCsr(csr)),
+    }
+}
+
+fn r_type(opcode: u32, rd: Register, funct3: u32, rs1: Register, rs2: Register, funct7: u32) -> u32 {
+    funct7 << 25 | (rs2 as u32) << 20 | (rs1 as u32) << 15 | funct3 << 12 | (rd as u32) << 7 | opcode
+}
+
+fn i_type(opcode: u32, rd: Register, funct3: u32, rs1: Register, imm: i64) -> Result<u32, EncodeError> {
+    let imm11_0 = check_imm(imm, 12, 1, true)?;
+    Ok(imm11_0 << 20 | (rs1 as u32) << 15 | funct3 << 12 | (rd as u32) << 7 | opcode)
+}
+
+fn shift(opcode: u32, rd: Register, funct3: u32, rs1: Register, shamt: u64, funct6: u32, bits: u32) -> Result<u32, EncodeError> {
+    let shamt = check_imm(shamt as i64, bits, 1, false)?;
+    Ok(funct6 << 26 | shamt << 20 | (rs1 as u32) << 15 | funct3 << 12 | (rd as u32) << 7 | opcode)
+}
+
+fn s_type(funct3: u32, rs1: Register, rs2: Register, imm: i64) -> Result<u32, EncodeError> {
+    let imm = check_imm(imm, 12, 1, true)?;
+    let imm11_5 = imm >> 5;
+    let imm4_0 = imm & 0b1_1111;
+    Ok(imm11_5 << 25 | (rs2 as u32) << 20 | (rs1 as u32) << 15 | funct3 << 12 | imm4_0 << 7 | STORE)
+}
+
+fn b_type(funct3: u32, rs1: Register, rs2: Register, imm: i64) -> Result<u32, EncodeError> {
+    let imm = check_imm(imm, 13, 2, true)?;
+    let imm12 = (imm >> 12) & 0b1;
+    let imm11 = (imm >> 11) & 0b1;
+    let imm10_5 = (imm >> 5) & 0b11_1111;
+    let imm4_1 = (imm >> 1) & 0b1111;
+    Ok(imm12 << 31 | imm10_5 << 25 | (rs2 as u32) << 20 | (rs1 as u32) << 15 | funct3 << 12 | imm4_1 << 8 | imm11 << 7 | BRANCH)
+}
+
+fn j_type(rd: Register, imm: i64) -> Result<u32, EncodeError> {
+    let imm = check_imm(imm, 21, 2, true)?;
+    let imm20 = (imm >> 20) & 0b1;
+    let imm19_12 = (imm >> 12) & 0b1111_1111;
+    let imm11 = (imm >> 11) & 0b1;
+    let imm10_1 = (imm >> 1) & 0b11_1111_1111;
+    Ok(imm20 << 31 | imm10_1 << 21 | imm11 << 20 | imm19_12 << 12 | (rd as u32) << 7 | JAL)
+}
+
+fn amo(funct5: u32, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> u32 {
+    r_type(AMO, rd, 0b010, rs1, rs2, funct5 << 2 | (aq as u32) << 1 | rl as u32)
+}
+
+fn csr(funct3: u32, rd: Register, rs1: u32, csr: CsrRegister) -> Result<u32, EncodeError> {
+    Ok(csr_address(csr)? << 20 | rs1 << 15 | funct3 << 12 | (rd as u32) << 7 | SYSTEM)
+}
+
+fn csr_imm(funct3: u32, rd: Register, imm: i64, csr_reg: CsrRegister) -> Result<u32, EncodeError> {
+    csr(funct3, rd, check_imm(imm, 5, 1, false)?, csr_reg)
+}
+
+fn system(funct12: u32) -> u32 {
+    funct12 << 20 | SYSTEM
+}
+
+impl Instruction {
+    /// Encodes the instruction as a 32-bit word. For everything [`crate::decode`]
+    /// returns, `decode(inst.encode()?) == Ok(inst)`.
+    pub fn encode(&self) -> Result<u32, EncodeError> {
+        Ok(match *self {
+            Add { rd, rs1, rs2 } => r_type(OP, rd, 0b000, rs1, rs2, 0b0000000),
+            Sub { rd, rs1, rs2 } => r_type(OP, rd, 0b000, rs1, rs2, 0b0100000),
+            Sll { rd, rs1, rs2 } => r_type(OP, rd, 0b001, rs1, rs2, 0b0000000),
+            Slt { rd, rs1, rs2 } => r_type(OP, rd, 0b010, rs1, rs2, 0b0000000),
+            Sltu { rd, rs1, rs2 } => r_type(OP, rd, 0b011, rs1, rs2, 0b0000000),
+            Xor { rd, rs1, rs2 } => r_type(OP, rd, 0b100, rs1, rs2, 0b0000000),
+            Srl { rd, rs1, rs2 } => r_type(OP, rd, 0b101, rs1, rs2, 0b0000000),
+            Sra { rd, rs1, rs2 } => r_type(OP, rd, 0b101, rs1, rs2, 0b0100000),
+            Or { rd, rs1, rs2 } => r_type(OP, rd, 0b110, rs1, rs2, 0b0000000),
+            And { rd, rs1, rs2 } => r_type(OP, rd, 0b111, rs1, rs2, 0b0000000),
+
+            Addw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b000, rs1, rs2, 0b0000000),
+            Subw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b000, rs1, rs2, 0b0100000),
+            Sllw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b001, rs1, rs2, 0b0000000),
+            Srlw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b101, rs1, rs2, 0b0000000),
+            Sraw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b101, rs1, rs2, 0b0100000),
+
+            Mul { rd, rs1, rs2 } => r_type(OP, rd, 0b000, rs1, rs2, 0b0000001),
+            Mulh { rd, rs1, rs2 } => r_type(OP, rd, 0b001, rs1, rs2, 0b0000001),
+            Mulhsu { rd, rs1, rs2 } => r_type(OP, rd, 0b010, rs1, rs2, 0b0000001),
+            Mulhu { rd, rs1, rs2 } => r_type(OP, rd, 0b011, rs1, rs2, 0b0000001),
+            Div { rd, rs1, rs2 } => r_type(OP, rd, 0b100, rs1, rs2, 0b0000001),
+            Divu { rd, rs1, rs2 } => r_type(OP, rd, 0b101, rs1, rs2, 0b0000001),
+            Rem { rd, rs1, rs2 } => r_type(OP, rd, 0b110, rs1, rs2, 0b0000001),
+            Remu { rd, rs1, rs2 } => r_type(OP, rd, 0b111, rs1, rs2, 0b0000001),
+
+            Mulw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b000, rs1, rs2, 0b0000001),
+            Divw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b100, rs1, rs2, 0b0000001),
+            Divuw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b101, rs1, rs2, 0b0000001),
+            Remw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b110, rs1, rs2, 0b0000001),
+            Remuw { rd, rs1, rs2 } => r_type(OP_32, rd, 0b111, rs1, rs2, 0b0000001),
+
+            Addi { rd, rs1, imm } => i_type(OP_IMM, rd, 0b000, rs1, imm)?,
+            Slti { rd, rs1, imm } => i_type(OP_IMM, rd, 0b010, rs1, imm)?,
+            Sltiu { rd, rs1, imm } => i_type(OP_IMM, rd, 0b011, rs1, imm)?,
+            Xori { rd, rs1, imm } => i_type(OP_IMM, rd, 0b100, rs1, imm)?,
+            Ori { rd, rs1, imm } => i_type(OP_IMM, rd, 0b110, rs1, imm)?,
+            Andi { rd, rs1, imm } => i_type(OP_IMM, rd, 0b111, rs1, imm)?,
+            Slli { rd, rs1, shamt } => shift(OP_IMM, rd, 0b001, rs1, shamt, 0b000000, 6)?,
+            Srli { rd, rs1, shamt } => shift(OP_IMM, rd, 0b101, rs1, shamt, 0b000000, 6)?,
+            Srai { rd, rs1, shamt } => shift(OP_IMM, rd, 0b101, rs1, shamt, 0b010000, 6)?,
+
+            Addiw { rd, rs1, imm } => i_type(OP_IMM_32, rd, 0b000, rs1, imm)?,
+            Slliw { rd, rs1, shamt } => shift(OP_IMM_32, rd, 0b001, rs1, shamt, 0b000000, 5)?,
+            Srliw { rd, rs1, shamt } => shift(OP_IMM_32, rd, 0b101, rs1, shamt, 0b000000, 5)?,
+            Sraiw { rd, rs1, shamt } => shift(OP_IMM_32, rd, 0b101, rs1, shamt, 0b010000, 5)?,
+
+            Sb { rs1, rs2, imm } => s_type(0b000, rs1, rs2, imm)?,
+            Sh { rs1, rs2, imm } => s_type(0b001, rs1, rs2, imm)?,
+            Sw { rs1, rs2, imm } => s_type(0b010, rs1, rs2, imm)?,
+            Sd { rs1, rs2, imm } => s_type(0b011, rs1, rs2, imm)?,
+
+            Lb { rd, rs1, imm } => i_type(LOAD, rd, 0b000, rs1, imm)?,
+            Lh { rd, rs1, imm } => i_type(LOAD, rd, 0b001, rs1, imm)?,
+            Lw { rd, rs1, imm } => i_type(LOAD, rd, 0b010, rs1, imm)?,
+            Ld { rd, rs1, imm } => i_type(LOAD, rd, 0b011, rs1, imm)?,
+            Lbu { rd, rs1, imm } => i_type(LOAD, rd, 0b100, rs1, imm)?,
+            Lhu { rd, rs1, imm } => i_type(LOAD, rd, 0b101, rs1, imm)?,
+            Lwu { rd, rs1, imm } => i_type(LOAD, rd, 0b110, rs1, imm)?,
+
+            Beq { rs1, rs2, imm } => b_type(0b000, rs1, rs2, imm)?,
+            Bne { rs1, rs2, imm } => b_type(0b001, rs1, rs2, imm)?,
+            Blt { rs1, rs2, imm } => b_type(0b100, rs1, rs2, imm)?,
+            Bge { rs1, rs2, imm } => b_type(0b101, rs1, rs2, imm)?,
+            Bltu { rs1, rs2, imm } => b_type(0b110, rs1, rs2, imm)?,
+            Bgeu { rs1, rs2, imm } => b_type(0b111, rs1, rs2, imm)?,
+
+            Auipc { rd, imm } => check_imm(imm, 20, 1, true)? << 12 | (rd as u32) << 7 | AUIPC,
+            Lui { rd, uimm } => check_imm(uimm as i64, 20, 1, false)? << 12 | (rd as u32) << 7 | LUI,
+            Jalr { rd, rs1, imm } => i_type(JALR, rd, 0b000, rs1, imm)?,
+            Jal { rd, imm } => j_type(rd, imm)?,
+
+            Amoaddw { rd, rs1, rs2, aq, rl } => amo(0b00000, rd, rs1, rs2, aq, rl),
+            Amoswapw { rd, rs1, rs2, aq, rl } => amo(0b00001, rd, rs1, rs2, aq, rl),
+            Amoxorw { rd, rs1, rs2, aq, rl } => amo(0b00100, rd, rs1, rs2, aq, rl),
+            Amoorw { rd, rs1, rs2, aq, rl } => amo(0b01000, rd, rs1, rs2, aq, rl),
+            Amoandw { rd, rs1, rs2, aq, rl } => amo(0b01100, rd, rs1, rs2, aq, rl),
+            Amominw { rd, rs1, rs2, aq, rl } => amo(0b10000, rd, rs1, rs2, aq, rl),
+            Amomaxw { rd, rs1, rs2, aq, rl } => amo(0b10100, rd, rs1, rs2, aq, rl),
+            Amominuw { rd, rs1, rs2, aq, rl } => amo(0b11000, rd, rs1, rs2, aq, rl),
+            Amomaxuw { rd, rs1, rs2, aq, rl } => amo(0b11100, rd, rs1, rs2, aq, rl),
+
+            Ecall => system(0x000),
+            Ebreak => system(0x001),
+            Uret => system(0x002),
+            Sret => system(0x102),
+            Wfi => system(0x105),
+            Mret => system(0x302),
+            Mnret => system(0x702),
+            Dret => system(0x7B2),
+            Csrrw { rd, rs1, csr: csr_reg } => csr(0b001, rd, rs1 as u32, csr_reg)?,
+            Csrrs { rd, rs1, csr: csr_reg } => csr(0b010, rd, rs1 as u32, csr_reg)?,
+            Csrrc { rd, rs1, csr: csr_reg } => csr(0b011, rd, rs1 as u32, csr_reg)?,
+            Csrrwi { rd, csr: csr_reg, imm } => csr_imm(0b101, rd, imm, csr_reg)?,
+            Csrrsi { rd, csr: csr_reg, imm } => csr_imm(0b110, rd, imm, csr_reg)?,
+            Csrrci { rd, csr: csr_reg, imm } => csr_imm(0b111, rd, imm, csr_reg)?,
+
+            FenceI => 0b001 << 12 | MISC_MEM,
+            Fence { pred, succ } => (u8::from(pred) as u32) << 24 | (u8::from(succ) as u32) << 20 | MISC_MEM,
+            FenceTso => 0b1000_0011_0011 << 20 | MISC_MEM,
+
+            Long { .. } => return Err(EncodeError::NoEncoding),
+        })
+    }
+}
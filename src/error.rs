@@ -0,0 +1,295 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::csr::CsrRegister;
+use crate::extension::Extension;
+use crate::opcode_size;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DecodeErrorKind {
+    /// No extension defines this encoding, or it is explicitly illegal (e.g. all zeros).
+    Illegal,
+    /// The encoding is reserved by the spec, e.g. `c.addi4spn` with a zero immediate.
+    Reserved,
+    /// The instruction belongs to an extension that is disabled.
+    ExtensionDisabled(Extension),
+    /// The encoding is valid RISC-V, but this crate can't decode it yet.
+    Unimplemented,
+    /// There are fewer bytes than the length encoding asks for.
+    Truncated,
+}
+
+/// An instruction field, used to point out which part of an encoding was rejected.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Field {
+    Opcode,
+    Rd,
+    Rs1,
+    Rs2,
+    Funct2,
+    Funct3,
+    Funct4,
+    Funct5,
+    Funct6,
+    Funct7,
+    Funct12,
+    Imm,
+    Shamt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DecodeError {
+    pub kind: DecodeErrorKind,
+    /// The instruction bits, compressed instructions are zero-extended.
+    pub raw: u32,
+    /// Length of the instruction in bytes, as given by the length encoding.
+    /// 0 for the reserved >=192-bit length encoding.
+    pub len: usize,
+    /// Bits [6:0] of the instruction. For compressed instructions this is
+    /// `funct3 << 2 | op`, the same value the compressed decoder dispatches on.
+    pub opcode: u8,
+    /// The field that made decoding fail, and its value.
+    pub field: Option<(Field, u32)>,
+}
+
+impl DecodeError {
+    pub(crate) fn new(kind: DecodeErrorKind, raw: u32, field: Option<(Field, u32)>) -> Self {
+        let len = opcode_size(raw);
+        let opcode = if len == 2 {
+            ((raw >> 11) & 0b11100 | raw & 0b11) as u8
+        } else {
+            (raw & 0x7F) as u8
+        };
+        Self { kind, raw, len, opcode, field }
+    }
+
+    pub(crate) fn illegal(raw: u32, field: Field, value: u32) -> Self {
+        Self::new(DecodeErrorKind::Illegal, raw, Some((field, value)))
+    }
+
+    pub(crate) fn reserved(raw: u32, field: Field, value: u32) -> Self {
+        Self::new(DecodeErrorKind::Reserved, raw, Some((field, value)))
+    }
+
+    /// The reserved >=192-bit length encoding, bits [6:0] are all ones and nnn in
+    /// bits [14:12] is 0b111. Its length is unknown, so `len` is 0.
+    pub(crate) fn reserved_length(raw: u32) -> Self {
+        let field = Some((Field::Funct3, (raw >> 12) & 0b111));
+        Self { kind: DecodeErrorKind::Reserved, raw, len: 0, opcode: (raw & 0x7F) as u8, field }
+    }
+
+    pub(crate) fn unimplemented(raw: u32) -> Self {
+        Self::new(DecodeErrorKind::Unimplemented, raw, None)
+    }
+
+    pub(crate) fn truncated(raw: u32) -> Self {
+        Self::new(DecodeErrorKind::Truncated, raw, None)
+    }
+}
+
+impl Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeErrorKind::Illegal => write!(f, "illegal"),
+            DecodeErrorKind::Reserved => write!(f, "reserved"),
+            DecodeErrorKind::ExtensionDisabled(ext) => write!(f, "{} extension disabled for", ext),
+            DecodeErrorKind::Unimplemented => write!(f, "unimplemented"),
+            DecodeErrorKind::Truncated => write!(f, "truncated"),
+        }
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.len == 0 {
+            write!(f, "{} >=192-bit length encoding 0x{:08x}", self.kind, self.raw)?;
+        } else {
+            write!(f, "{} {}-bit instruction 0x{:0w$x}", self.kind, self.len*8, self.raw, w = self.len.min(4)*2)?;
+        }
+        if let Some((field, value)) = self.field {
+            write!(f, ": {} = 0x{:x}", format!("{:?}", field).to_lowercase(), value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DecodeError {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EncodeError {
+    /// The immediate doesn't fit in its field.
+    ImmOutOfRange { imm: i64, min: i64, max: i64 },
+    /// The immediate has bits set below the field's alignment, e.g. an odd branch offset.
+    ImmMisaligned { imm: i64, align: i64 },
+    /// The CSR has no 12-bit address.
+    InvalidCsr(CsrRegister),
+    /// The instruction has no encoding of the requested size.
+    NoEncoding,
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::ImmOutOfRange { imm, min, max } => write!(f, "immediate {} out of range {}..={}", imm, min, max),
+            EncodeError::ImmMisaligned { imm, align } => write!(f, "immediate {} is not a multiple of {}", imm, align),
+            EncodeError::InvalidCsr(csr) => write!(f, "CSR {:?} has no address", csr),
+            EncodeError::NoEncoding => write!(f, "instruction has no encoding of this size"),
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// No instruction has this mnemonic.
+    UnknownMnemonic(String),
+    /// The mnemonic takes a different number of operands.
+    OperandCount { expected: usize, found: usize },
+    InvalidRegister(String),
+    InvalidImm(String),
+    InvalidCsr(String),
+    /// An operand with the wrong shape, e.g. a load address without `(reg)`.
+    InvalidOperand(String),
+    /// The operands parsed, but the instruction can't be encoded with them.
+    Encode(EncodeError),
+}
+
+impl From<EncodeError> for ParseError {
+    fn from(err: EncodeError) -> Self {
+        ParseError::Encode(err)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnknownMnemonic(name) => write!(f, "unknown mnemonic `{}`", name),
+            ParseError::OperandCount { expected, found } => write!(f, "expected {} operands, found {}", expected, found),
+            ParseError::InvalidRegister(reg) => write!(f, "invalid register `{}`", reg),
+            ParseError::InvalidImm(imm) => write!(f, "invalid immediate `{}`", imm),
+            ParseError::InvalidCsr(csr) => write!(f, "invalid CSR `{}`", csr),
+            ParseError::InvalidOperand(op) => write!(f, "invalid operand `{}`", op),
+            ParseError::Encode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmErrorKind {
+    Parse(ParseError),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    UnknownDirective(String),
+    /// `.org` can only move forward.
+    OrgBackwards { org: u64, address: u64 },
+    /// The next address doesn't fit in 64 bits.
+    AddressOverflow,
+    /// `.org` would pad more than 16 MiB.
+    FillTooLarge { len: u64 },
+}
+
+impl From<ParseError> for AsmErrorKind {
+    fn from(err: ParseError) -> Self {
+        AsmErrorKind::Parse(err)
+    }
+}
+
+/// An assembler error and the 1-based source line it happened on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl Display for AsmErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsmErrorKind::Parse(err) => write!(f, "{}", err),
+            AsmErrorKind::UndefinedLabel(label) => write!(f, "undefined label `{}`", label),
+            AsmErrorKind::DuplicateLabel(label) => write!(f, "label `{}` defined twice", label),
+            AsmErrorKind::UnknownDirective(name) => write!(f, "unknown directive `{}`", name),
+            AsmErrorKind::OrgBackwards { org, address } => write!(f, ".org 0x{:x} is before the current address 0x{:x}", org, address),
+            AsmErrorKind::AddressOverflow => write!(f, "address overflows 64 bits"),
+            AsmErrorKind::FillTooLarge { len } => write!(f, "padding of 0x{:x} bytes is too large", len),
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ElfError {
+    /// The file doesn't start with the ELF magic number.
+    NotElf,
+    /// `EI_CLASS` is neither ELFCLASS32 nor ELFCLASS64.
+    InvalidClass(u8),
+    BigEndian,
+    /// `e_machine` isn't EM_RISCV.
+    UnsupportedMachine(u16),
+    /// A header or table extends past the end of the file.
+    Truncated,
+}
+
+impl Display for ElfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ElfError::NotElf => write!(f, "not an ELF file"),
+            ElfError::InvalidClass(class) => write!(f, "invalid ELF class {}", class),
+            ElfError::BigEndian => write!(f, "big-endian ELF files are not supported"),
+            ElfError::UnsupportedMachine(machine) => write!(f, "not a RISC-V ELF file (e_machine {})", machine),
+            ElfError::Truncated => write!(f, "ELF file is truncated"),
+        }
+    }
+}
+
+impl Error for ElfError {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ImageErrorKind {
+    /// The line isn't a record: bad start code, odd length or non-hex digits.
+    InvalidRecord,
+    /// The record's byte count doesn't match its length.
+    Length,
+    Checksum { expected: u8, found: u8 },
+    UnknownRecordType(u8),
+    /// The record's data goes past the end of the 64-bit address space.
+    AddressOverflow,
+    /// The record writes to `address`, which an earlier record already holds.
+    Overlap { address: u64 },
+}
+
+/// An Intel HEX or S-record error and the 1-based line it happened on.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImageError {
+    pub line: usize,
+    pub kind: ImageErrorKind,
+}
+
+impl Display for ImageErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageErrorKind::InvalidRecord => write!(f, "invalid record"),
+            ImageErrorKind::Length => write!(f, "byte count doesn't match the record length"),
+            ImageErrorKind::Checksum { expected, found } => write!(f, "checksum is 0x{:02x}, expected 0x{:02x}", found, expected),
+            ImageErrorKind::UnknownRecordType(kind) => write!(f, "unknown record type {}", kind),
+            ImageErrorKind::AddressOverflow => write!(f, "data goes past the end of the address space"),
+            ImageErrorKind::Overlap { address } => write!(f, "data at 0x{:x} overlaps an earlier record", address),
+        }
+    }
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl Error for ImageError {}
@@ -0,0 +1,318 @@
+use std::fmt::{Display, Formatter};
+use crate::decoder::Decoder;
+use crate::error::{DecodeError, Field};
+use crate::format::{FormatOptions, Plain};
+use crate::instruction::{DecodedInstruction, InstructionFormat};
+use crate::optype::OpType;
+use crate::register::Register;
+use crate::instruction_length;
+
+/// A run of bits in an encoding. Scattered immediates are split into one
+/// field per run, named after the immediate bits they hold, e.g. `imm[10:5]`
+/// or `uimm[4:2|7:6]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitField {
+    pub name: &'static str,
+    /// Most and least significant bit of the field in the instruction.
+    pub hi: u8,
+    pub lo: u8,
+    pub value: u32,
+    /// What a [`DecodeError`] calls this field.
+    pub field: Option<Field>,
+}
+
+impl BitField {
+    fn new(raw: u32, name: &'static str, hi: u8, lo: u8, field: Option<Field>) -> Self {
+        let value = (raw >> lo) & (u32::MAX >> (31 - (hi - lo)));
+        Self { name, hi, lo, value, field }
+    }
+
+    /// The register a register field selects, `rd'` style fields are x8-x15.
+    pub fn register(&self) -> Option<Register> {
+        match self.name {
+            "rd" | "rs1" | "rs2" | "rs3" | "rd/rs1" => Some(Register::from(self.value as usize)),
+            "rd'" | "rs1'" | "rs2'" | "rd'/rs1'" => Some(Register::from_rvc(self.value as u8)),
+            _ => None,
+        }
+    }
+}
+
+/// The bit-field breakdown of an instruction, see [`explain`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub raw: u32,
+    /// Length in bytes, 0 for the reserved >=192-bit length encoding.
+    pub len: usize,
+    pub format: InstructionFormat,
+    /// Fields from the most significant bit down.
+    pub fields: Vec<BitField>,
+    /// The immediate put together from its fields, for the formats that have one.
+    pub imm: Option<i64>,
+    pub result: Result<DecodedInstruction, DecodeError>,
+}
+
+impl Explanation {
+    /// The fields that made decoding fail. For RVC, an opcode error means the
+    /// combination of `funct3` and `op`.
+    pub fn culprits(&self) -> Vec<&BitField> {
+        let Some((field, _)) = self.result.as_ref().err().and_then(|err| err.field) else {
+            return Vec::new();
+        };
+        let compressed = self.len == 2;
+        self.fields
+            .iter()
+            .filter(|bits| bits.field == Some(field) || (compressed && field == Field::Opcode && bits.name == "funct3"))
+            .collect()
+    }
+}
+
+/// Lays out the fields of `raw` for its format, the immediate fields are in the
+/// order [`OpType`] assembles them from.
+pub(crate) fn fields(raw: u32, format: InstructionFormat) -> Vec<BitField> {
+    use InstructionFormat::*;
+    use Field::*;
+    let opcode = raw & 0x7F;
+    let funct3 = (raw >> 12) & 0b111;
+    let layout: &[(&'static str, u8, u8, Option<Field>)] = match format {
+        R if opcode == 0x2F => &[
+            ("funct5", 31, 27, Some(Funct5)), ("aq", 26, 26, None), ("rl", 25, 25, None),
+            ("rs2", 24, 20, Some(Rs2)), ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+        ],
+        R => &[
+            ("funct7", 31, 25, Some(Funct7)), ("rs2", 24, 20, Some(Rs2)), ("rs1", 19, 15, Some(Rs1)),
+            ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+        ],
+        R4 => &[
+            ("rs3", 31, 27, None), ("funct2", 26, 25, Some(Funct2)), ("rs2", 24, 20, Some(Rs2)),
+            ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+        ],
+        I => match (opcode, funct3) {
+            (0x13, 0b001 | 0b101) => &[
+                ("funct6", 31, 26, Some(Funct6)), ("shamt", 25, 20, Some(Shamt)),
+                ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+            ],
+            (0x1B, 0b001 | 0b101) => &[
+                ("funct7", 31, 25, Some(Funct7)), ("shamt", 24, 20, Some(Shamt)),
+                ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+            ],
+            (0x73, 0b000) => &[
+                ("funct12", 31, 20, Some(Funct12)), ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+            ],
+            (0x73, 0b101..=0b111) => &[
+                ("csr", 31, 20, Some(Imm)), ("uimm", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+            ],
+            (0x73, _) => &[
+                ("csr", 31, 20, Some(Imm)), ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+            ],
+            (0x0F, 0b000) => &[
+                ("fm", 31, 28, Some(Imm)), ("pred", 27, 24, None), ("succ", 23, 20, None),
+                ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+            ],
+            _ => &[
+                ("imm[11:0]", 31, 20, Some(Imm)), ("rs1", 19, 15, Some(Rs1)), ("funct3", 14, 12, Some(Funct3)), ("rd", 11, 7, Some(Rd)),
+            ],
+        },
+        S => &[
+            ("imm[11:5]", 31, 25, Some(Imm)), ("rs2", 24, 20, Some(Rs2)), ("rs1", 19, 15, Some(Rs1)),
+            ("funct3", 14, 12, Some(Funct3)), ("imm[4:0]", 11, 7, Some(Imm)),
+        ],
+        B => &[
+            ("imm[12]", 31, 31, Some(Imm)), ("imm[10:5]", 30, 25, Some(Imm)), ("rs2", 24, 20, Some(Rs2)), ("rs1", 19, 15, Some(Rs1)),
+            ("funct3", 14, 12, Some(Funct3)), ("imm[4:1]", 11, 8, Some(Imm)), ("imm[11]", 7, 7, Some(Imm)),
+        ],
+        U => &[("imm[31:12]", 31, 12, Some(Imm)), ("rd", 11, 7, Some(Rd))],
+        J => &[
+            ("imm[20]", 31, 31, Some(Imm)), ("imm[10:1]", 30, 21, Some(Imm)), ("imm[11]", 20, 20, Some(Imm)),
+            ("imm[19:12]", 19, 12, Some(Imm)), ("rd", 11, 7, Some(Rd)),
+        ],
+        // Only the length encoding is known, nnn = 0b111 is the reserved one
+        Long if instruction_length(raw as u16).is_none() => &[("nnn", 14, 12, Some(Funct3))],
+        Long => &[],
+        CR => &[("funct4", 15, 12, Some(Funct4)), ("rd/rs1", 11, 7, Some(Rd)), ("rs2", 6, 2, Some(Rs2))],
+        // RVC immediates are named after the bits they hold, as in the spec
+        CI => match (raw & 0b11, (raw >> 13) & 0b111) {
+            (0b01, 0b011) if (raw >> 7) & 0b11111 == 2 => &[
+                ("funct3", 15, 13, Some(Funct3)), ("nzimm[9]", 12, 12, Some(Imm)), ("rd/rs1", 11, 7, Some(Rd)),
+                ("nzimm[4|6|8:7|5]", 6, 2, Some(Imm)),
+            ],
+            (0b01, 0b011) => &[
+                ("funct3", 15, 13, Some(Funct3)), ("nzimm[17]", 12, 12, Some(Imm)), ("rd/rs1", 11, 7, Some(Rd)),
+                ("nzimm[16:12]", 6, 2, Some(Imm)),
+            ],
+            (0b10, 0b000) => &[
+                ("funct3", 15, 13, Some(Funct3)), ("shamt[5]", 12, 12, Some(Shamt)), ("rd/rs1", 11, 7, Some(Rd)),
+                ("shamt[4:0]", 6, 2, Some(Shamt)),
+            ],
+            (0b10, 0b010) => &[
+                ("funct3", 15, 13, Some(Funct3)), ("uimm[5]", 12, 12, Some(Imm)), ("rd", 11, 7, Some(Rd)),
+                ("uimm[4:2|7:6]", 6, 2, Some(Imm)),
+            ],
+            (0b10, _) => &[
+                ("funct3", 15, 13, Some(Funct3)), ("uimm[5]", 12, 12, Some(Imm)), ("rd", 11, 7, Some(Rd)),
+                ("uimm[4:3|8:6]", 6, 2, Some(Imm)),
+            ],
+            _ => &[("funct3", 15, 13, Some(Funct3)), ("imm[5]", 12, 12, Some(Imm)), ("rd/rs1", 11, 7, Some(Rd)), ("imm[4:0]", 6, 2, Some(Imm))],
+        },
+        CSS if (raw >> 13) & 0b111 == 0b110 => &[("funct3", 15, 13, Some(Funct3)), ("uimm[5:2|7:6]", 12, 7, Some(Imm)), ("rs2", 6, 2, Some(Rs2))],
+        CSS => &[("funct3", 15, 13, Some(Funct3)), ("uimm[5:3|8:6]", 12, 7, Some(Imm)), ("rs2", 6, 2, Some(Rs2))],
+        CIW => &[("funct3", 15, 13, Some(Funct3)), ("nzuimm[5:4|9:6|2|3]", 12, 5, Some(Imm)), ("rd'", 4, 2, Some(Rd))],
+        CL if (raw >> 13) & 0b111 == 0b010 => &[
+            ("funct3", 15, 13, Some(Funct3)), ("uimm[5:3]", 12, 10, Some(Imm)), ("rs1'", 9, 7, Some(Rs1)),
+            ("uimm[2|6]", 6, 5, Some(Imm)), ("rd'", 4, 2, Some(Rd)),
+        ],
+        CL => &[
+            ("funct3", 15, 13, Some(Funct3)), ("uimm[5:3]", 12, 10, Some(Imm)), ("rs1'", 9, 7, Some(Rs1)),
+            ("uimm[7:6]", 6, 5, Some(Imm)), ("rd'", 4, 2, Some(Rd)),
+        ],
+        CS if (raw >> 13) & 0b111 == 0b110 => &[
+            ("funct3", 15, 13, Some(Funct3)), ("uimm[5:3]", 12, 10, Some(Imm)), ("rs1'", 9, 7, Some(Rs1)),
+            ("uimm[2|6]", 6, 5, Some(Imm)), ("rs2'", 4, 2, Some(Rs2)),
+        ],
+        CS => &[
+            ("funct3", 15, 13, Some(Funct3)), ("uimm[5:3]", 12, 10, Some(Imm)), ("rs1'", 9, 7, Some(Rs1)),
+            ("uimm[7:6]", 6, 5, Some(Imm)), ("rs2'", 4, 2, Some(Rs2)),
+        ],
+        CA => &[("funct6", 15, 10, Some(Funct6)), ("rd'/rs1'", 9, 7, Some(Rd)), ("funct2", 6, 5, Some(Funct2)), ("rs2'", 4, 2, Some(Rs2))],
+        CB if (raw >> 13) & 0b111 == 0b100 && (raw >> 10) & 0b11 == 0b10 => &[
+            ("funct3", 15, 13, Some(Funct3)), ("imm[5]", 12, 12, Some(Imm)), ("funct2", 11, 10, Some(Funct2)),
+            ("rd'/rs1'", 9, 7, Some(Rd)), ("imm[4:0]", 6, 2, Some(Imm)),
+        ],
+        CB if (raw >> 13) & 0b111 == 0b100 => &[
+            ("funct3", 15, 13, Some(Funct3)), ("shamt[5]", 12, 12, Some(Shamt)), ("funct2", 11, 10, Some(Funct2)),
+            ("rd'/rs1'", 9, 7, Some(Rd)), ("shamt[4:0]", 6, 2, Some(Shamt)),
+        ],
+        CB => &[
+            ("funct3", 15, 13, Some(Funct3)), ("offset[8|4:3]", 12, 10, Some(Imm)), ("rs1'", 9, 7, Some(Rs1)),
+            ("offset[7:6|2:1|5]", 6, 2, Some(Imm)),
+        ],
+        CJ => &[("funct3", 15, 13, Some(Funct3)), ("offset[11|4|9:8|10|6|7|3:1|5]", 12, 2, Some(Imm))],
+    };
+    let op = match instruction_length(raw as u16) {
+        Some(2) => ("op", 1, 0, Some(Field::Opcode)),
+        _ => ("opcode", 6, 0, Some(Field::Opcode)),
+    };
+    layout.iter().chain([&op]).map(|&(name, hi, lo, field)| BitField::new(raw, name, hi, lo, field)).collect()
+}
+
+/// The immediate of the base formats, assembled by the decoder's own [`OpType`] constructors.
+fn imm(raw: u32, fields: &[BitField]) -> Option<i64> {
+    match InstructionFormat::of(raw) {
+        InstructionFormat::I if fields.iter().any(|field| field.name == "imm[11:0]") => match OpType::new_i(raw) {
+            OpType::I { imm, .. } => Some(imm),
+            _ => None,
+        },
+        InstructionFormat::S => match OpType::new_s(raw) {
+            OpType::S { imm, .. } => Some(imm),
+            _ => None,
+        },
+        InstructionFormat::B => match OpType::new_b(raw) {
+            OpType::B { imm, .. } => Some(imm),
+            _ => None,
+        },
+        InstructionFormat::U => match OpType::new_u(raw) {
+            OpType::U { imm, .. } => Some(imm << 12),
+            _ => None,
+        },
+        InstructionFormat::J => match OpType::new_j(raw) {
+            OpType::J { imm, .. } => Some(imm),
+            _ => None,
+        },
+        _ if instruction_length(raw as u16) == Some(2) => rvc_imm(fields),
+        _ => None,
+    }
+}
+
+/// Puts an RVC immediate together from the bits its field names list, e.g.
+/// `uimm[4:2|7:6]` holds bits 4 to 2 then 7 and 6. `imm`, `nzimm` and `offset`
+/// are sign-extended from their top bit.
+fn rvc_imm(fields: &[BitField]) -> Option<i64> {
+    let mut value = 0u64;
+    let mut top = None;
+    let mut signed = false;
+    for field in fields {
+        let Some((name, bits)) = field.name.strip_suffix(']').and_then(|name| name.split_once('[')) else {
+            continue;
+        };
+        signed = matches!(name, "imm" | "nzimm" | "offset");
+        // Walk the field from its most significant bit
+        let mut pos = field.hi - field.lo + 1;
+        for part in bits.split('|') {
+            let (hi, lo) = match part.split_once(':') {
+                Some((hi, lo)) => (hi.parse::<u8>().ok()?, lo.parse::<u8>().ok()?),
+                None => (part.parse::<u8>().ok()?, part.parse::<u8>().ok()?),
+            };
+            for bit in (lo..=hi).rev() {
+                pos -= 1;
+                value |= ((field.value as u64 >> pos) & 1) << bit;
+                top = top.max(Some(bit));
+            }
+        }
+    }
+    let shift = 63 - top? as u32;
+    Some(match signed {
+        true => (value << shift) as i64 >> shift,
+        false => value as i64,
+    })
+}
+
+impl Decoder {
+    /// Breaks `raw` into its fields and decodes it. Compressed instructions
+    /// are in the low 16 bits, longer ones only get their opcode explained.
+    pub fn explain(&self, raw: u32) -> Explanation {
+        let len = instruction_length(raw as u16).unwrap_or(0);
+        let raw = if len == 2 { raw & 0xFFFF } else { raw };
+        let format = InstructionFormat::of(raw);
+        let fields = fields(raw, format);
+        let imm = imm(raw, &fields);
+        Explanation { raw, len, format, fields, imm, result: self.decode_full(raw) }
+    }
+}
+
+/// [`Decoder::explain`] for RV64 with every extension enabled.
+pub fn explain(raw: u32) -> Explanation {
+    Decoder::new().explain(raw)
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = match self.len {
+            0 => 8,
+            len => len.min(4) * 2,
+        };
+        let size = match self.len {
+            0 => ">=192-bit".to_string(),
+            len => format!("{}-bit", len * 8),
+        };
+        match &self.result {
+            Ok(decoded) => {
+                // Aliases would hide fields, e.g. the `zero` operand of `bnez`
+                let text = decoded.instruction.display_with(&FormatOptions::default().without_aliases(), &Plain);
+                writeln!(f, "0x{:0w$x}: {} ({}, {} format)", self.raw, text, size, self.format, w = width)?;
+            }
+            Err(_) => writeln!(f, "0x{:0w$x}: ({}, {} format)", self.raw, size, self.format, w = width)?,
+        }
+        let culprits = self.culprits();
+        for field in &self.fields {
+            let bits = match field.hi == field.lo {
+                true => format!("{}", field.hi),
+                false => format!("{}:{}", field.hi, field.lo),
+            };
+            let binary = format!("{:0w$b}", field.value, w = (field.hi - field.lo + 1) as usize);
+            write!(f, "  {:>5}  {:<10} {:>20}  0x{:x}", bits, field.name, binary, field.value)?;
+            if let Some(reg) = field.register() {
+                write!(f, "  {}", reg.abi_name())?;
+            }
+            if culprits.contains(&field) {
+                write!(f, "  <- {}", self.result.as_ref().unwrap_err().kind)?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(imm) = self.imm {
+            writeln!(f, "  imm = {}", imm)?;
+        }
+        if let Err(err) = &self.result {
+            writeln!(f, "  error: {}", err)?;
+        }
+        Ok(())
+    }
+}
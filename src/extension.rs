@@ -0,0 +1,74 @@
+use std::fmt::{Display, Formatter};
+use crate::extension::Extension::*;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Extension {
+    I,
+    M,
+    A,
+    F,
+    D,
+    Q,
+    C,
+    V,
+    H,
+    N,
+    S,
+    Zicsr,
+    Zifencei,
+    Zba,
+    Zbb,
+    Zbc,
+    Zbs,
+    Smrnmi,
+    Sdext,
+    /// Non-standard encodings, e.g. [`crate::Instruction::Long`]
+    Custom,
+}
+
+impl Extension {
+    pub const ALL: [Extension; 20] = [
+        I, M, A, F, D, Q, C, V, H, N, S, Zicsr, Zifencei, Zba, Zbb, Zbc, Zbs, Smrnmi, Sdext, Custom,
+    ];
+
+    /// Returns the extension for a single-letter `misa` bit or ISA string letter.
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Some(match letter.to_ascii_lowercase() {
+            'i' | 'e' => I,
+            'm' => M,
+            'a' => A,
+            'f' => F,
+            'd' => D,
+            'q' => Q,
+            'c' => C,
+            'v' => V,
+            'h' => H,
+            'n' => N,
+            's' => S,
+            'x' => Custom,
+            _ => return None,
+        })
+    }
+
+    /// Returns the extensions this one depends on, e.g. D needs F.
+    pub fn implies(&self) -> &'static [Extension] {
+        match self {
+            F => &[Zicsr],
+            D => &[F, Zicsr],
+            Q => &[D, F, Zicsr],
+            V => &[D, F, Zicsr],
+            H => &[S],
+            _ => &[],
+        }
+    }
+}
+
+impl Display for Extension {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Custom => write!(f, "x"),
+            _ => write!(f, "{}", format!("{:?}", self).to_lowercase()),
+        }
+    }
+}
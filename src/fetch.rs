@@ -0,0 +1,66 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::decoder::Decoder;
+use crate::error::DecodeError;
+use crate::instruction::DecodedInstruction;
+use crate::{opcode_size, MAX_OPCODE_SIZE};
+
+/// Instruction memory, e.g. an emulator's MMU. Instructions are fetched one
+/// 16-bit parcel at a time, so a fault can be pinned to the parcel that caused it.
+pub trait Fetch {
+    type Error;
+
+    /// Reads the little-endian halfword at `address`.
+    fn fetch(&mut self, address: u64) -> Result<u16, Self::Error>;
+}
+
+impl<E, F: FnMut(u64) -> Result<u16, E>> Fetch for F {
+    type Error = E;
+
+    fn fetch(&mut self, address: u64) -> Result<u16, Self::Error> {
+        self(address)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchError<E> {
+    /// Fetching a parcel failed. `parcel` is 0 for the first halfword, and
+    /// `address` is the faulting address, which is what `mtval` should be set to.
+    Fault { parcel: usize, address: u64, error: E },
+    Decode(DecodeError),
+}
+
+impl<E: Display> Display for FetchError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Fault { parcel, address, error } => write!(f, "fetch of parcel {} at 0x{:x} failed: {}", parcel, address, error),
+            FetchError::Decode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<E: Debug + Display> Error for FetchError<E> {}
+
+impl Decoder {
+    /// Fetches and decodes the instruction at `pc`. Parcels after the first are
+    /// only fetched if the length encoding says the instruction needs them.
+    pub fn decode_fetch<F: Fetch>(&self, fetch: &mut F, pc: u64) -> Result<DecodedInstruction, FetchError<F::Error>> {
+        let mut bytes = [0u8; MAX_OPCODE_SIZE];
+        let mut len = 0;
+        for parcel in 0..MAX_OPCODE_SIZE / 2 {
+            if parcel > 0 && len >= opcode_size(u16::from_le_bytes([bytes[0], bytes[1]]) as u32) {
+                break;
+            }
+            let address = pc.wrapping_add(parcel as u64 * 2);
+            let half = fetch.fetch(address).map_err(|error| FetchError::Fault { parcel, address, error })?;
+            bytes[len..len + 2].copy_from_slice(&half.to_le_bytes());
+            len += 2;
+        }
+        self.decode_full_bytes(&bytes[..len]).map_err(FetchError::Decode)
+    }
+}
+
+/// [`Decoder::decode_fetch`] for RV64 with every extension enabled.
+pub fn decode_fetch<F: Fetch>(fetch: &mut F, pc: u64) -> Result<DecodedInstruction, FetchError<F::Error>> {
+    Decoder::new().decode_fetch(fetch, pc)
+}
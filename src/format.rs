@@ -0,0 +1,404 @@
+use std::fmt::{Display, Formatter, Write};
+use crate::csr::CsrRegister;
+use crate::instruction::{FenceFlags, Instruction};
+use crate::instruction::Instruction::*;
+use crate::register::Register;
+
+/// One piece of a formatted instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Mnemonic(String),
+    Register(Register),
+    Imm(i64),
+    /// An unsigned field like a shift amount or `lui` immediate, objdump prints these in hex.
+    UImm(u64),
+    /// An absolute branch or jump target.
+    Address(u64),
+    Csr(CsrRegister),
+    /// A load or store address, `offset(base)`.
+    Memory { offset: i64, base: Register },
+    /// Punctuation between the mnemonic and operands, like `, `.
+    Separator(&'static str),
+    /// Anything else, e.g. fence sets or the bytes of a `.insn`.
+    Text(String),
+}
+
+/// Which assembler dialect to print.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Syntax {
+    /// The crate's own syntax, registers as `a0/x10` and immediates as `dec(0xHEX)`.
+    #[default]
+    Native,
+    /// Matches `objdump -d` from GNU binutils, or `-M no-aliases` with
+    /// [`FormatOptions::aliases`] off. Compressed instructions only get their
+    /// `c.*` mnemonics when [`FormatOptions::compressed`] is set.
+    Objdump,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FormatOptions {
+    pub syntax: Syntax,
+    /// Address of the instruction. When set, branch and jump targets are
+    /// printed as absolute addresses instead of offsets.
+    pub pc: Option<u64>,
+    /// Print pseudo-instructions like `li`, `mv` and `ret` where one applies, on by default.
+    pub aliases: bool,
+    /// The RVC mnemonic the instruction was expanded from, see
+    /// [`crate::DecodedInstruction::compressed`]. Objdump syntax without aliases
+    /// prints it the way binutils does, `c.li a0,10` instead of `addi a0,zero,10`.
+    pub compressed: Option<&'static str>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { syntax: Syntax::Native, pc: None, aliases: true, compressed: None }
+    }
+}
+
+impl FormatOptions {
+    pub fn objdump() -> Self {
+        Self { syntax: Syntax::Objdump, ..Self::default() }
+    }
+
+    pub fn with_pc(self, pc: u64) -> Self {
+        Self { pc: Some(pc), ..self }
+    }
+
+    pub fn without_aliases(self) -> Self {
+        Self { aliases: false, ..self }
+    }
+
+    pub fn with_compressed(self, compressed: Option<&'static str>) -> Self {
+        Self { compressed, ..self }
+    }
+}
+
+/// Aliases reading a CSR, `rdcycle rd` is `csrrs rd, cycle, zero`.
+pub(crate) const CSR_READ_ALIASES: [(&str, CsrRegister); 6] = [
+    ("rdcycle", CsrRegister::Cycle),
+    ("rdtime", CsrRegister::Time),
+    ("rdinstret", CsrRegister::InstRet),
+    ("frflags", CsrRegister::FFlags),
+    ("frrm", CsrRegister::FRM),
+    ("frcsr", CsrRegister::FCSR),
+];
+
+/// Aliases swapping a CSR, `fsflags rd, rs` is `csrrw rd, fflags, rs`.
+pub(crate) const CSR_SWAP_ALIASES: [(&str, CsrRegister); 3] = [
+    ("fsflags", CsrRegister::FFlags),
+    ("fsrm", CsrRegister::FRM),
+    ("fscsr", CsrRegister::FCSR),
+];
+
+/// Immediate forms of [`CSR_SWAP_ALIASES`], `fsflagsi rd, imm` is `csrrwi rd, fflags, imm`.
+pub(crate) const CSR_SWAP_IMM_ALIASES: [(&str, CsrRegister); 2] = [
+    ("fsflagsi", CsrRegister::FFlags),
+    ("fsrmi", CsrRegister::FRM),
+];
+
+fn csr_alias(table: &[(&'static str, CsrRegister)], csr: CsrRegister) -> Option<&'static str> {
+    table.iter().find(|(_, reg)| *reg == csr).map(|(name, _)| *name)
+}
+
+impl Token {
+    /// The token as it's written in `syntax`.
+    pub fn text(&self, syntax: Syntax) -> String {
+        match (self, syntax) {
+            (Token::Mnemonic(name) | Token::Text(name), _) => name.clone(),
+            (Token::Separator(sep), _) => sep.to_string(),
+            (Token::Register(reg), Syntax::Native) => format!("{}/{}", reg.abi_name(), reg.numeric_name()),
+            (Token::Register(reg), Syntax::Objdump) => reg.abi_name().to_string(),
+            (Token::Imm(imm), Syntax::Native) => format!("{}(0x{:0X})", imm, *imm as u64),
+            (Token::Imm(imm), Syntax::Objdump) => imm.to_string(),
+            (Token::UImm(imm), Syntax::Native) => Token::Imm(*imm as i64).text(syntax),
+            (Token::UImm(imm), Syntax::Objdump) => format!("0x{:x}", imm),
+            (Token::Address(address), Syntax::Native) => format!("0x{:x}", address),
+            (Token::Address(address), Syntax::Objdump) => format!("{:x}", address),
+            (Token::Csr(CsrRegister::Other(address)), Syntax::Objdump) => format!("0x{:x}", address),
+            (Token::Csr(csr), _) => csr.name(),
+            (Token::Memory { offset, base }, _) => {
+                format!("{}({})", Token::Imm(*offset).text(syntax), Token::Register(*base).text(syntax))
+            }
+        }
+    }
+}
+
+/// Turns tokens into text. `text` is the token already formatted for the
+/// syntax in use, a renderer only decides how it's decorated.
+pub trait Render {
+    fn token(&self, out: &mut dyn Write, token: &Token, text: &str) -> std::fmt::Result;
+}
+
+/// Custom renderers can be closures.
+impl<F: Fn(&mut dyn Write, &Token, &str) -> std::fmt::Result> Render for F {
+    fn token(&self, out: &mut dyn Write, token: &Token, text: &str) -> std::fmt::Result {
+        self(out, token, text)
+    }
+}
+
+/// Writes the text as-is, this is what `Display` uses.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Plain;
+
+impl Render for Plain {
+    fn token(&self, out: &mut dyn Write, _token: &Token, text: &str) -> std::fmt::Result {
+        out.write_str(text)
+    }
+}
+
+/// Colors tokens with ANSI escape codes, for terminals.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Ansi;
+
+impl Render for Ansi {
+    fn token(&self, out: &mut dyn Write, token: &Token, text: &str) -> std::fmt::Result {
+        let color = match token {
+            Token::Mnemonic(_) => "38;5;196",
+            Token::Register(_) => "32",
+            Token::Imm(_) | Token::UImm(_) | Token::Address(_) => "94",
+            Token::Csr(_) => "35",
+            Token::Memory { .. } | Token::Separator(_) | Token::Text(_) => return out.write_str(text),
+        };
+        write!(out, "\x1B[{}m{}\x1B[0m", color, text)
+    }
+}
+
+/// Tokens paired with a renderer, ready to be printed with `{}`.
+pub struct Rendered<'a, R: ?Sized> {
+    tokens: Vec<Token>,
+    syntax: Syntax,
+    renderer: &'a R,
+}
+
+impl<'a, R: Render + ?Sized> Rendered<'a, R> {
+    pub fn new(tokens: Vec<Token>, syntax: Syntax, renderer: &'a R) -> Self {
+        Self { tokens, syntax, renderer }
+    }
+}
+
+impl<R: Render + ?Sized> Display for Rendered<'_, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for token in &self.tokens {
+            self.renderer.token(f, token, &token.text(self.syntax))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a mnemonic and its operands, `add a0, a1, a2` in the native syntax
+/// and `add\ta0,a1,a2` for objdump.
+fn op(syntax: Syntax, name: &str, operands: &[Token]) -> Vec<Token> {
+    let (first, rest) = match syntax {
+        Syntax::Native => (" ", ", "),
+        Syntax::Objdump => ("\t", ","),
+    };
+    let mut tokens = vec![Token::Mnemonic(name.to_string())];
+    for (i, operand) in operands.iter().enumerate() {
+        tokens.push(Token::Separator(if i == 0 { first } else { rest }));
+        tokens.push(operand.clone());
+    }
+    tokens
+}
+
+fn ordering_suffix(aq: bool, rl: bool) -> &'static str {
+    match (aq, rl) {
+        (false, false) => "",
+        (true, false) => ".aq",
+        (false, true) => ".rl",
+        (true, true) => ".aqrl",
+    }
+}
+
+impl Instruction {
+    /// Splits the instruction into tokens, in the same syntax as `Display`.
+    pub fn tokens(&self) -> Vec<Token> {
+        self.tokens_with(&FormatOptions::default())
+    }
+
+    pub fn tokens_with(&self, options: &FormatOptions) -> Vec<Token> {
+        use Token::{Imm, Memory};
+        let syntax = options.syntax;
+        let objdump = syntax == Syntax::Objdump;
+        let op = |name: &str, operands: &[Token]| op(syntax, name, operands);
+        let reg = Token::Register;
+        let target = |imm: i64| match options.pc {
+            Some(pc) => Token::Address(pc.wrapping_add(imm as u64)),
+            None => Imm(imm),
+        };
+        let shamt = |shamt: u64| if objdump { Token::UImm(shamt) } else { Imm(shamt as i64) };
+        if options.aliases {
+            if let Some((name, operands)) = self.alias(options) {
+                return op(name, &operands);
+            }
+        } else if let (Some(name), true) = (options.compressed, objdump) {
+            return self.compressed_tokens(name, options);
+        }
+        let name = self.mnemonic();
+        match *self {
+            Add { rd, rs1, rs2 } | Sub { rd, rs1, rs2 } | Sll { rd, rs1, rs2 } | Slt { rd, rs1, rs2 } |
+            Sltu { rd, rs1, rs2 } | Xor { rd, rs1, rs2 } | Srl { rd, rs1, rs2 } | Sra { rd, rs1, rs2 } |
+            Or { rd, rs1, rs2 } | And { rd, rs1, rs2 } |
+            Addw { rd, rs1, rs2 } | Subw { rd, rs1, rs2 } | Sllw { rd, rs1, rs2 } | Srlw { rd, rs1, rs2 } | Sraw { rd, rs1, rs2 } |
+            Mul { rd, rs1, rs2 } | Mulh { rd, rs1, rs2 } | Mulhsu { rd, rs1, rs2 } | Mulhu { rd, rs1, rs2 } |
+            Div { rd, rs1, rs2 } | Divu { rd, rs1, rs2 } | Rem { rd, rs1, rs2 } | Remu { rd, rs1, rs2 } |
+            Mulw { rd, rs1, rs2 } | Divw { rd, rs1, rs2 } | Divuw { rd, rs1, rs2 } | Remw { rd, rs1, rs2 } | Remuw { rd, rs1, rs2 } => {
+                op(name, &[reg(rd), reg(rs1), reg(rs2)])
+            }
+
+            Addi { rd, rs1, imm } | Slti { rd, rs1, imm } | Sltiu { rd, rs1, imm } | Xori { rd, rs1, imm } |
+            Ori { rd, rs1, imm } | Andi { rd, rs1, imm } | Addiw { rd, rs1, imm } => op(name, &[reg(rd), reg(rs1), Imm(imm)]),
+            Slli { rd, rs1, shamt: amount } | Srli { rd, rs1, shamt: amount } | Srai { rd, rs1, shamt: amount } |
+            Slliw { rd, rs1, shamt: amount } | Srliw { rd, rs1, shamt: amount } | Sraiw { rd, rs1, shamt: amount } => {
+                op(name, &[reg(rd), reg(rs1), shamt(amount)])
+            }
+
+            Sb { rs1, rs2, imm } | Sh { rs1, rs2, imm } | Sw { rs1, rs2, imm } | Sd { rs1, rs2, imm } => {
+                op(name, &[reg(rs2), Memory { offset: imm, base: rs1 }])
+            }
+            Lb { rd, rs1, imm } | Lh { rd, rs1, imm } | Lw { rd, rs1, imm } | Lbu { rd, rs1, imm } |
+            Lhu { rd, rs1, imm } | Lwu { rd, rs1, imm } | Ld { rd, rs1, imm } => {
+                op(name, &[reg(rd), Memory { offset: imm, base: rs1 }])
+            }
+
+            Beq { rs1, rs2, imm } | Bne { rs1, rs2, imm } | Blt { rs1, rs2, imm } |
+            Bltu { rs1, rs2, imm } | Bge { rs1, rs2, imm } | Bgeu { rs1, rs2, imm } => op(name, &[reg(rs1), reg(rs2), target(imm)]),
+
+            // objdump prints the 20-bit field, not the sign-extended value
+            Auipc { rd, imm } if objdump => op(name, &[reg(rd), Token::UImm(imm as u64 & 0xFFFFF)]),
+            Auipc { rd, imm } => op(name, &[reg(rd), Imm(imm)]),
+            Lui { rd, uimm } if objdump => op(name, &[reg(rd), Token::UImm(uimm)]),
+            Lui { rd, uimm } => op(name, &[reg(rd), Imm(uimm as i64)]),
+            Jalr { rd, rs1, imm } if objdump => op(name, &[reg(rd), Memory { offset: imm, base: rs1 }]),
+            Jalr { rd, rs1, imm } => op(name, &[reg(rd), reg(rs1), Imm(imm)]),
+            Jal { rd, imm } => op(name, &[reg(rd), target(imm)]),
+
+            Amoswapw { rd, rs1, rs2, aq, rl } | Amoaddw { rd, rs1, rs2, aq, rl } | Amoxorw { rd, rs1, rs2, aq, rl } |
+            Amoandw { rd, rs1, rs2, aq, rl } | Amoorw { rd, rs1, rs2, aq, rl } | Amominw { rd, rs1, rs2, aq, rl } |
+            Amomaxw { rd, rs1, rs2, aq, rl } | Amominuw { rd, rs1, rs2, aq, rl } | Amomaxuw { rd, rs1, rs2, aq, rl } => {
+                let name = if objdump { format!("{}{}", name, ordering_suffix(aq, rl)) } else { name.to_string() };
+                let mut tokens = op(&name, &[reg(rd), reg(rs2), Token::Separator("(")]);
+                tokens.extend([reg(rs1), Token::Separator(")")]);
+                if !objdump {
+                    tokens.push(Token::Text(format!(" (aq:{} rq:{})", aq, rl)));
+                }
+                tokens
+            }
+
+            Ecall | Ebreak | Uret | Sret | Wfi | Mret | Mnret | Dret | FenceI | FenceTso => op(name, &[]),
+            Csrrw { rd, rs1, csr } | Csrrs { rd, rs1, csr } | Csrrc { rd, rs1, csr } => op(name, &[reg(rd), Token::Csr(csr), reg(rs1)]),
+            Csrrwi { rd, csr, imm } | Csrrsi { rd, csr, imm } | Csrrci { rd, csr, imm } => op(name, &[reg(rd), Token::Csr(csr), Imm(imm)]),
+            Fence { pred, succ } => {
+                let set = |flags: FenceFlags| match flags.to_string() {
+                    empty if empty.is_empty() && objdump => Token::Text("0".to_string()),
+                    set => Token::Text(set),
+                };
+                op(name, &[set(pred), set(succ)])
+            }
+            Long { len, raw } => {
+                let hex: String = raw[..len].iter().rev().map(|b| format!("{:02x}", b)).collect();
+                // binutils keeps the space after the comma for `.insn`
+                let mut tokens = op(name, &[Token::Text(len.to_string())]);
+                tokens.extend([Token::Separator(", "), Token::Text(format!("0x{}", hex))]);
+                tokens
+            }
+        }
+    }
+
+    /// `c.*` mnemonics keep only the operands the compressed form encodes, e.g.
+    /// `c.addi a0,1` for `addi a0,a0,1` and `c.jr ra` for `jalr zero,0(ra)`.
+    fn compressed_tokens(&self, name: &'static str, options: &FormatOptions) -> Vec<Token> {
+        let operands: Vec<Token> = self.tokens_with(&options.with_compressed(None))
+            .into_iter()
+            .filter(|token| !matches!(token, Token::Mnemonic(_) | Token::Separator(_)))
+            .collect();
+        let operands = match (name, *self) {
+            ("c.nop", _) => vec![],
+            ("c.jr" | "c.jalr", Jalr { rs1, .. }) => vec![Token::Register(rs1)],
+            ("c.j", _) => operands[1..].to_vec(),
+            ("c.addi4spn", _) => operands,
+            // The second operand is rd again, or zero for c.li, c.mv and c.beqz/c.bnez
+            _ if operands.len() == 3 => vec![operands[0].clone(), operands[2].clone()],
+            _ => operands,
+        };
+        op(options.syntax, name, &operands)
+    }
+
+    /// Picks the pseudo-instruction binutils would print, if one applies.
+    fn alias(&self, options: &FormatOptions) -> Option<(&'static str, Vec<Token>)> {
+        use Register::{ReturnAddress, Zero};
+        use Token::Imm;
+        let reg = Token::Register;
+        let target = |imm: i64| match options.pc {
+            Some(pc) => Token::Address(pc.wrapping_add(imm as u64)),
+            None => Imm(imm),
+        };
+        Some(match *self {
+            Addi { rd: Zero, rs1: Zero, imm: 0 } => ("nop", vec![]),
+            Addi { rd, rs1: Zero, imm } => ("li", vec![reg(rd), Imm(imm)]),
+            Addi { rd, rs1, imm: 0 } => ("mv", vec![reg(rd), reg(rs1)]),
+            // binutils only has this form for c.mv, a full `add` keeps its name
+            Add { rd, rs1: Zero, rs2 } if options.compressed == Some("c.mv") => ("mv", vec![reg(rd), reg(rs2)]),
+            Addiw { rd, rs1, imm: 0 } => ("sext.w", vec![reg(rd), reg(rs1)]),
+            Xori { rd, rs1, imm: -1 } => ("not", vec![reg(rd), reg(rs1)]),
+            Andi { rd, rs1, imm: 255 } => ("zext.b", vec![reg(rd), reg(rs1)]),
+            Sub { rd, rs1: Zero, rs2 } => ("neg", vec![reg(rd), reg(rs2)]),
+            Subw { rd, rs1: Zero, rs2 } => ("negw", vec![reg(rd), reg(rs2)]),
+            Sltiu { rd, rs1, imm: 1 } => ("seqz", vec![reg(rd), reg(rs1)]),
+            Sltu { rd, rs1: Zero, rs2 } => ("snez", vec![reg(rd), reg(rs2)]),
+            Slt { rd, rs1, rs2: Zero } => ("sltz", vec![reg(rd), reg(rs1)]),
+            Slt { rd, rs1: Zero, rs2 } => ("sgtz", vec![reg(rd), reg(rs2)]),
+
+            Beq { rs1, rs2: Zero, imm } => ("beqz", vec![reg(rs1), target(imm)]),
+            Bne { rs1, rs2: Zero, imm } => ("bnez", vec![reg(rs1), target(imm)]),
+            Bge { rs1: Zero, rs2, imm } => ("blez", vec![reg(rs2), target(imm)]),
+            Bge { rs1, rs2: Zero, imm } => ("bgez", vec![reg(rs1), target(imm)]),
+            Blt { rs1, rs2: Zero, imm } => ("bltz", vec![reg(rs1), target(imm)]),
+            Blt { rs1: Zero, rs2, imm } => ("bgtz", vec![reg(rs2), target(imm)]),
+            Jal { rd: Zero, imm } => ("j", vec![target(imm)]),
+            Jal { rd: ReturnAddress, imm } => ("jal", vec![target(imm)]),
+            Jalr { rd: Zero, rs1: ReturnAddress, imm: 0 } => ("ret", vec![]),
+            Jalr { rd: Zero, rs1, imm: 0 } => ("jr", vec![reg(rs1)]),
+            Jalr { rd: ReturnAddress, rs1, imm: 0 } => ("jalr", vec![reg(rs1)]),
+            Jalr { rd: Zero, rs1, imm } => ("jr", vec![Token::Memory { offset: imm, base: rs1 }]),
+            Jalr { rd: ReturnAddress, rs1, imm } => ("jalr", vec![Token::Memory { offset: imm, base: rs1 }]),
+
+            Csrrs { rd, rs1: Zero, csr } => match csr_alias(&CSR_READ_ALIASES, csr) {
+                Some(name) => (name, vec![reg(rd)]),
+                None => ("csrr", vec![reg(rd), Token::Csr(csr)]),
+            },
+            Csrrw { rd: Zero, rs1: Zero, csr: CsrRegister::Cycle } => ("unimp", vec![]),
+            Csrrw { rd, rs1, csr } => match (csr_alias(&CSR_SWAP_ALIASES, csr), rd) {
+                (Some(name), Zero) => (name, vec![reg(rs1)]),
+                (Some(name), _) => (name, vec![reg(rd), reg(rs1)]),
+                (None, Zero) => ("csrw", vec![Token::Csr(csr), reg(rs1)]),
+                (None, _) => return None,
+            },
+            Csrrs { rd: Zero, rs1, csr } => ("csrs", vec![Token::Csr(csr), reg(rs1)]),
+            Csrrc { rd: Zero, rs1, csr } => ("csrc", vec![Token::Csr(csr), reg(rs1)]),
+            Csrrwi { rd, csr, imm } => match (csr_alias(&CSR_SWAP_IMM_ALIASES, csr), rd) {
+                (Some(name), Zero) => (name, vec![Imm(imm)]),
+                (Some(name), _) => (name, vec![reg(rd), Imm(imm)]),
+                (None, Zero) => ("csrwi", vec![Token::Csr(csr), Imm(imm)]),
+                (None, _) => return None,
+            },
+            Csrrsi { rd: Zero, csr, imm } => ("csrsi", vec![Token::Csr(csr), Imm(imm)]),
+            Csrrci { rd: Zero, csr, imm } => ("csrci", vec![Token::Csr(csr), Imm(imm)]),
+
+            Fence { pred, succ } => match (u8::from(pred), u8::from(succ)) {
+                (0b1111, 0b1111) => ("fence", vec![]),
+                (0b0001, 0b0000) => ("pause", vec![]),
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+
+    /// Formats the instruction with `renderer`, e.g. `inst.display(&Plain).to_string()`.
+    pub fn display<'a, R: Render + ?Sized>(&self, renderer: &'a R) -> Rendered<'a, R> {
+        self.display_with(&FormatOptions::default(), renderer)
+    }
+
+    pub fn display_with<'a, R: Render + ?Sized>(&self, options: &FormatOptions, renderer: &'a R) -> Rendered<'a, R> {
+        Rendered::new(self.tokens_with(options), options.syntax, renderer)
+    }
+}
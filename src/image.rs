@@ -0,0 +1,186 @@
+use crate::error::{ImageError, ImageErrorKind};
+use crate::listing::Listing;
+use crate::stream::InstructionStream;
+
+/// A contiguous run of bytes and the address it is loaded at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub address: u64,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    /// Address just past the last byte, `None` if that is past the 64-bit address space.
+    pub fn end(&self) -> Option<u64> {
+        self.address.checked_add(self.data.len() as u64)
+    }
+
+    /// Decodes the segment as RV64 with every extension enabled, use
+    /// [`InstructionStream::with_decoder`] on `data` for anything else.
+    pub fn stream(&self) -> InstructionStream<'_> {
+        InstructionStream::new(&self.data, self.address)
+    }
+
+    pub fn listing(&self) -> Listing<'_> {
+        Listing::new(&self.data, self.address)
+    }
+}
+
+/// Memory contents from an Intel HEX, Motorola S-record or raw binary file.
+/// Segments are sorted by address and records that follow each other are merged,
+/// records that overlap an earlier one are an error.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    /// Start address from the file, if it has one.
+    pub entry: Option<u64>,
+}
+
+/// Decodes the hex digits of a record after its start code.
+fn record_bytes(digits: &str) -> Result<Vec<u8>, ImageErrorKind> {
+    if digits.len() % 2 != 0 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ImageErrorKind::InvalidRecord);
+    }
+    Ok((0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap()).collect())
+}
+
+fn big_endian(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, byte| value << 8 | *byte as u64)
+}
+
+fn check_sum(expected: u8, found: u8) -> Result<(), ImageErrorKind> {
+    match expected == found {
+        true => Ok(()),
+        false => Err(ImageErrorKind::Checksum { expected, found }),
+    }
+}
+
+impl Image {
+    /// Detects the format from the contents: Intel HEX starts with `:`, S-records
+    /// with `S0`-`S9`, and anything else is a raw binary loaded at `base`.
+    pub fn parse(bytes: &[u8], base: u64) -> Result<Self, ImageError> {
+        let text = std::str::from_utf8(bytes).ok().map(str::trim_start);
+        match text {
+            Some(text) if text.starts_with(':') => Self::from_ihex(text),
+            Some(text) if text.starts_with('S') && text[1..].starts_with(|c: char| c.is_ascii_digit()) => Self::from_srec(text),
+            _ => Ok(Self::from_binary(bytes, base)),
+        }
+    }
+
+    pub fn from_binary(bytes: &[u8], base: u64) -> Self {
+        let mut image = Self::default();
+        if !bytes.is_empty() {
+            image.segments.push(Segment { address: base, data: bytes.to_vec() });
+        }
+        image
+    }
+
+    /// Parses Intel HEX, including the extended segment (`02`) and extended
+    /// linear (`04`) address records used for images above 64 KiB.
+    pub fn from_ihex(text: &str) -> Result<Self, ImageError> {
+        let mut image = Self::default();
+        let mut base = 0u64;
+        for (index, line) in text.lines().enumerate() {
+            let err = |kind| ImageError { line: index + 1, kind };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bytes = record_bytes(line.strip_prefix(':').ok_or(err(ImageErrorKind::InvalidRecord))?).map_err(err)?;
+            // :LLAAAATT, data, checksum
+            if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+                return Err(err(ImageErrorKind::Length));
+            }
+            let (record, checksum) = bytes.split_at(bytes.len() - 1);
+            let sum = record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            check_sum(sum.wrapping_neg(), checksum[0]).map_err(err)?;
+            let address = big_endian(&record[1..3]);
+            let data = &record[4..];
+            let expected = match record[3] {
+                0x02 | 0x04 => Some(2),
+                0x03 | 0x05 => Some(4),
+                _ => None,
+            };
+            if expected.is_some_and(|len| data.len() != len) {
+                return Err(err(ImageErrorKind::Length));
+            }
+            match record[3] {
+                0x00 => image.add(base.checked_add(address).ok_or(err(ImageErrorKind::AddressOverflow))?, data).map_err(err)?,
+                0x01 => break,
+                0x02 => base = big_endian(data) << 4,
+                // CS:IP
+                0x03 => image.entry = Some((big_endian(data) >> 16 << 4) + (big_endian(data) & 0xFFFF)),
+                0x04 => base = big_endian(data) << 16,
+                0x05 => image.entry = Some(big_endian(data)),
+                kind => return Err(err(ImageErrorKind::UnknownRecordType(kind))),
+            }
+        }
+        image.merge();
+        Ok(image)
+    }
+
+    /// Parses Motorola S-records. S1/S2/S3 carry data with 16/24/32-bit
+    /// addresses and S9/S8/S7 the start address, headers and counts are skipped.
+    pub fn from_srec(text: &str) -> Result<Self, ImageError> {
+        let mut image = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let err = |kind| ImageError { line: index + 1, kind };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let record = line.strip_prefix('S').ok_or(err(ImageErrorKind::InvalidRecord))?;
+            let kind = record.chars().next().and_then(|c| c.to_digit(10)).ok_or(err(ImageErrorKind::InvalidRecord))? as u8;
+            let bytes = record_bytes(&record[1..]).map_err(err)?;
+            let width = match kind {
+                0 | 1 | 5 | 9 => 2,
+                2 | 6 | 8 => 3,
+                3 | 7 => 4,
+                kind => return Err(err(ImageErrorKind::UnknownRecordType(kind))),
+            };
+            // Stnn, address, data, checksum
+            if bytes.len() < width + 2 || bytes.len() != bytes[0] as usize + 1 {
+                return Err(err(ImageErrorKind::Length));
+            }
+            let (record, checksum) = bytes.split_at(bytes.len() - 1);
+            check_sum(!record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)), checksum[0]).map_err(err)?;
+            let address = big_endian(&record[1..1 + width]);
+            match kind {
+                1..=3 => image.add(address, &record[1 + width..]).map_err(err)?,
+                7..=9 => image.entry = Some(address),
+                _ => {}
+            }
+        }
+        image.merge();
+        Ok(image)
+    }
+
+    fn add(&mut self, address: u64, data: &[u8]) -> Result<(), ImageErrorKind> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let end = address.checked_add(data.len() as u64).ok_or(ImageErrorKind::AddressOverflow)?;
+        // Segments added here always have an end
+        if let Some(other) = self.segments.iter().find(|other| other.address < end && other.end() > Some(address)) {
+            return Err(ImageErrorKind::Overlap { address: address.max(other.address) });
+        }
+        match self.segments.last_mut() {
+            Some(last) if last.end() == Some(address) => last.data.extend_from_slice(data),
+            _ => self.segments.push(Segment { address, data: data.to_vec() }),
+        }
+        Ok(())
+    }
+
+    /// Sorts the segments and joins the ones that are contiguous.
+    fn merge(&mut self) {
+        self.segments.sort_by_key(|segment| segment.address);
+        let mut merged: Vec<Segment> = Vec::with_capacity(self.segments.len());
+        for segment in self.segments.drain(..) {
+            match merged.last_mut() {
+                Some(last) if last.end() == Some(segment.address) => last.data.extend(segment.data),
+                _ => merged.push(segment),
+            }
+        }
+        self.segments = merged;
+    }
+}
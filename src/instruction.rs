@@ -1,10 +1,15 @@
 use std::fmt::{Display, Formatter};
 use crate::csr::CsrRegister;
+use crate::error::DecodeError;
+use crate::extension::Extension;
+use crate::format::Plain;
 use crate::instruction::Instruction::*;
 use crate::optype::OpType;
 use crate::register::Register;
+use crate::MAX_OPCODE_SIZE;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instruction {
     Add{rd: Register, rs1: Register, rs2: Register},
     Sub{rd: Register, rs1: Register, rs2: Register},
@@ -95,6 +100,8 @@ pub enum Instruction {
     Sret,
     Wfi,
     Mret,
+    Mnret,
+    Dret,
     Csrrw {rd: Register, rs1: Register, csr: CsrRegister},
     Csrrs {rd: Register, rs1: Register, csr: CsrRegister},
     Csrrc {rd: Register, rs1: Register, csr: CsrRegister},
@@ -104,6 +111,104 @@ pub enum Instruction {
 
     FenceI,
     Fence {pred: FenceFlags, succ: FenceFlags},
+    /// `fence rw,rw` with fm = 0b1000, ordering like RVTSO.
+    FenceTso,
+
+    /// A 48-bit or longer instruction. No standard extension uses these yet, so the
+    /// little-endian bytes are kept as-is, `raw[len..]` is zero.
+    Long {len: usize, raw: [u8; MAX_OPCODE_SIZE]},
+}
+
+/// An [`Instruction`] together with how it was encoded.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DecodedInstruction {
+    pub instruction: Instruction,
+    /// The first 32 bits of the encoding, compressed instructions are zero-extended.
+    pub raw: u32,
+    /// Length of the encoding in bytes, this is how far the PC advances.
+    pub len: usize,
+    /// The RVC mnemonic, e.g. `c.addi`, if this was expanded from a compressed instruction.
+    pub compressed: Option<&'static str>,
+}
+
+impl DecodedInstruction {
+    pub fn is_compressed(&self) -> bool {
+        self.compressed.is_some()
+    }
+
+    pub fn format(&self) -> InstructionFormat {
+        InstructionFormat::of(self.raw)
+    }
+}
+
+/// The base and RVC encoding formats, i.e. how the fields are laid out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum InstructionFormat {
+    R,
+    /// Fused multiply-add, R with a third source register in bits [31:27].
+    R4,
+    I,
+    S,
+    B,
+    U,
+    J,
+    CR,
+    CI,
+    CSS,
+    CIW,
+    CL,
+    CS,
+    CA,
+    CB,
+    CJ,
+    /// 48-bit or longer, the layout is up to the extension. Also used for the
+    /// reserved >=192-bit length encoding.
+    Long,
+}
+
+impl InstructionFormat {
+    /// The format of the instruction starting with `raw`, going by its opcode
+    /// (and funct3 for RVC) only, so this works for undecodable words too.
+    pub fn of(raw: u32) -> Self {
+        use InstructionFormat::*;
+        let funct3 = (raw >> 13) & 0b111;
+        if crate::instruction_length(raw as u16).map_or(true, |len| len > 4) {
+            return Long;
+        }
+        match raw & 0b11 {
+            0b00 => match funct3 {
+                0b000 => CIW,
+                0b001..=0b100 => CL,
+                _ => CS,
+            },
+            0b01 => match funct3 {
+                0b100 if (raw >> 10) & 0b11 == 0b11 => CA,
+                0b100 | 0b110 | 0b111 => CB,
+                0b101 => CJ,
+                _ => CI,
+            },
+            0b10 => match funct3 {
+                0b100 => CR,
+                0b101..=0b111 => CSS,
+                _ => CI,
+            },
+            _ => match raw & 0x7F {
+                0x23 | 0x27 => S,
+                0x63 => B,
+                0x37 | 0x17 => U,
+                0x6F => J,
+                0x33 | 0x3B | 0x2F | 0x53 | 0x57 => R,
+                0x43 | 0x47 | 0x4B | 0x4F => R4,
+                _ => I,
+            },
+        }
+    }
+}
+
+impl Display for InstructionFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(&format!("{:?}", self))
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -119,6 +224,12 @@ impl From<u8> for FenceFlags {
     }
 }
 
+impl From<FenceFlags> for u8 {
+    fn from(flags: FenceFlags) -> Self {
+        (flags.i as u8) << 3 | (flags.o as u8) << 2 | (flags.r as u8) << 1 | flags.w as u8
+    }
+}
+
 impl Display for FenceFlags {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}{}{}{}",
@@ -130,100 +241,15 @@ impl Display for FenceFlags {
     }
 }
 
-struct Num(i64);
-
-impl Display for Num {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "\x1B[94m{}\x1B[0m(\x1B[94m0x{:0X}\x1B[0m)", self.0, self.0 as u64)
-    }
-}
-
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let _ = write!(f, "\x1B[38;5;196m");
-        let retval = match self {
-            Mul { rd, rs1, rs2 } => write!(f, "mul {}, {}, {}", rd, rs1, rs2),
-            Div { rd, rs1, rs2 } => write!(f, "div {}, {}, {}", rd, rs1, rs2),
-            Add { rd, rs1, rs2 } => write!(f, "add {}, {}, {}", rd, rs1, rs2),
-            Sub { rd, rs1, rs2 } => write!(f, "sub {}, {}, {}", rd, rs1, rs2),
-            Or { rd, rs1, rs2 } => write!(f, "or {}, {}, {}", rd, rs1, rs2),
-            Xor { rd, rs1, rs2 } => write!(f, "xor {}, {}, {}", rd, rs1, rs2),
-            And { rd, rs1, rs2 } => write!(f, "and {}, {}, {}", rd, rs1, rs2),
-            Sll { rd, rs1, rs2 } => write!(f, "sll {}, {}, {}", rd, rs1, rs2),
-            Slt { rd, rs1, rs2 } => write!(f, "slt {}, {}, {}", rd, rs1, rs2),
-            Sltu { rd, rs1, rs2 } => write!(f, "sltu {}, {}, {}", rd, rs1, rs2),
-            Srl { rd, rs1, rs2 } => write!(f, "srl {}, {}, {}", rd, rs1, rs2),
-            Sra { rd, rs1, rs2 } => write!(f, "sra {}, {}, {}", rd, rs1, rs2),
-            Addi { rd, rs1, imm } => write!(f, "addi {}, {}, {}", rd, rs1, Num(*imm)),
-            Slli { rd, rs1, shamt } => write!(f, "slli {}, {}, {}", rd, rs1, Num(*shamt as i64)),
-            Srli { rd, rs1, shamt } => write!(f, "srli {}, {}, {}", rd, rs1, Num(*shamt as i64)),
-            Ori { rd, rs1, imm } => write!(f, "ori {}, {}, {}", rd, rs1, Num(*imm)),
-            Andi { rd, rs1, imm } => write!(f, "andi {}, {}, {}", rd, rs1, Num(*imm)),
-            Addiw { rd, rs1, imm } => write!(f, "addiw {}, {}, {}", rd, rs1, Num(*imm)),
-            Slliw { rd, rs1, shamt } => write!(f, "slliw {}, {}, {}", rd, rs1, Num(*shamt as i64)),
-            Srliw { rd, rs1, shamt } => write!(f, "srliw {}, {}, {}", rd, rs1, Num(*shamt as i64)),
-            Sraiw { rd, rs1, shamt } => write!(f, "sraiw {}, {}, {}", rd, rs1, Num(*shamt as i64)),
-            Sb { rs1, rs2, imm } => write!(f, "sb {}, {}({})", rs2, Num(*imm), rs1),
-            Sh { rs1, rs2, imm } => write!(f, "sh {}, {}({})", rs2, Num(*imm), rs1),
-            Sw { rs1, rs2, imm } => write!(f, "sw {}, {}({})", rs2, Num(*imm), rs1),
-            Sd { rs1, rs2, imm } => write!(f, "sd {}, {}({})", rs2, Num(*imm), rs1),
-            Lb { rd, rs1, imm } => write!(f, "lb {}, {}({})", rd, Num(*imm), rs1),
-            Lbu { rd, rs1, imm } => write!(f, "lbu {}, {}({})", rd, Num(*imm), rs1),
-            Lh { rd, rs1, imm } => write!(f, "lh {}, {}({})", rd, Num(*imm), rs1),
-            Lhu { rd, rs1, imm } => write!(f, "lhu {}, {}({})", rd, Num(*imm), rs1),
-            Lw { rd, rs1, imm } => write!(f, "lw {}, {}({})", rd, Num(*imm), rs1),
-            Lwu { rd, rs1, imm } => write!(f, "lwu {}, {}({})", rd, Num(*imm), rs1),
-            Ld { rd, rs1, imm } => write!(f, "ld {}, {}({})", rd, Num(*imm), rs1),
-            Beq { rs1, rs2, imm } => write!(f, "beq {}, {}, {}", rs1, rs2, Num(*imm)),
-            Bne { rs1, rs2, imm } => write!(f, "bne {}, {}, {}", rs1, rs2, Num(*imm)),
-            Blt { rs1, rs2, imm } => write!(f, "blt {}, {}, {}", rs1, rs2, Num(*imm)),
-            Bltu { rs1, rs2, imm } => write!(f, "bltu {}, {}, {}", rs1, rs2, Num(*imm)),
-            Bge { rs1, rs2, imm } => write!(f, "bge {}, {}, {}", rs1, rs2, Num(*imm)),
-            Bgeu { rs1, rs2, imm } => write!(f, "bgeu {}, {}, {}", rs1, rs2, Num(*imm)),
-            Auipc { rd, imm } => write!(f, "auipc {}, {}", rd, Num(*imm)),
-            Lui { rd, uimm } => write!(f, "lui {}, {}", rd, Num(*uimm as i64)),
-            Jalr { rd, rs1, imm } => {
-                if rd == &Register::Zero && rs1 == &Register::ReturnAddress && *imm == 0 {
-                    write!(f, "ret")
-                } else {
-                    write!(f, "jalr {}, {}, {}", rd, rs1, Num(*imm))
-                }
-            }
-            Jal { rd, imm } => write!(f, "jal {}, {}", rd, Num(*imm)),
-
-            Amoswapw {rd, rs1, rs2, aq, rl} => write!(f, "amoswap.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amoaddw {rd, rs1, rs2, aq, rl} => write!(f, "amoadd.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amoxorw {rd, rs1, rs2, aq, rl} => write!(f, "amoxor.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amoandw {rd, rs1, rs2, aq, rl} => write!(f, "amoand.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amoorw {rd, rs1, rs2, aq, rl} => write!(f, "amoor.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amominw {rd, rs1, rs2, aq, rl} => write!(f, "amomin.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amomaxw {rd, rs1, rs2, aq, rl} => write!(f, "amomax.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amominuw {rd, rs1, rs2, aq, rl} => write!(f, "amominu.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-            Amomaxuw {rd, rs1, rs2, aq, rl} => write!(f, "amomaxu.w {}, {}, ({}) (aq:{} rq:{})", rd, rs2, rs1, aq, rl),
-
-            Ecall => write!(f, "ecall"),
-            Ebreak => write!(f, "Ebreak"),
-            Uret => write!(f, "uret"),
-            Sret => write!(f, "sret"),
-            Wfi => write!(f, "wfi"),
-            Mret => write!(f, "mret"),
-            Csrrw { rd, rs1, csr } => write!(f, "csrrw {}, {}, {}", rd, csr, rs1),
-            Csrrs { rd, rs1, csr } => write!(f, "csrrs {}, {}, {}", rd, csr, rs1),
-            Csrrc { rd, rs1, csr } => write!(f, "csrrc {}, {}, {}", rd, csr, rs1),
-            Csrrwi { rd, csr, imm } => write!(f, "csrrwi {}, {}, {}", rd, csr, Num(*imm)),
-            Csrrsi { rd, csr, imm } => write!(f, "csrrsi {}, {}, {}", rd, csr, Num(*imm)),
-            Csrrci { rd, csr, imm } => write!(f, "csrrci {}, {}, {}", rd, csr, Num(*imm)),
-            FenceI => write!(f, "fence.i"),
-            Fence { pred, succ } => write!(f, "fence {}, {}", pred, succ),
-            _ => write!(f, "TODO: impl display for {:?}", self)
-        };
-        let _ = write!(f, "\x1B[0m");
-        retval
+        write!(f, "{}", self.display(&Plain))
     }
 }
 
 impl Instruction {
-    pub(crate) fn opcode_type(full_opcode: u32) -> Result<OpType, String> {
+    #[allow(dead_code)]
+    pub(crate) fn opcode_type(full_opcode: u32) -> Result<OpType, DecodeError> {
         let opcode = full_opcode & 0b0111_1111;
         Ok(match opcode {
             0x37 => OpType::new_lui(full_opcode),
@@ -240,8 +266,136 @@ impl Instruction {
             0x63 => OpType::new_b(full_opcode),
             0x33 | 0x3b => OpType::new_r(full_opcode),
             _ => {
-                return Err(format!("Unknown opcode: 0b{opcode:07b} (0x{opcode:02X})"))
+                return Err(DecodeError::unimplemented(full_opcode))
             }
         })
     }
+
+    /// The base mnemonic, e.g. `amoswap.w`, without ordering suffixes or aliases.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Add { .. } => "add",
+            Sub { .. } => "sub",
+            Sll { .. } => "sll",
+            Slt { .. } => "slt",
+            Sltu { .. } => "sltu",
+            Xor { .. } => "xor",
+            Srl { .. } => "srl",
+            Sra { .. } => "sra",
+            Or { .. } => "or",
+            And { .. } => "and",
+            Addw { .. } => "addw",
+            Subw { .. } => "subw",
+            Sllw { .. } => "sllw",
+            Srlw { .. } => "srlw",
+            Sraw { .. } => "sraw",
+            Mul { .. } => "mul",
+            Mulh { .. } => "mulh",
+            Mulhsu { .. } => "mulhsu",
+            Mulhu { .. } => "mulhu",
+            Div { .. } => "div",
+            Divu { .. } => "divu",
+            Rem { .. } => "rem",
+            Remu { .. } => "remu",
+            Mulw { .. } => "mulw",
+            Divw { .. } => "divw",
+            Divuw { .. } => "divuw",
+            Remw { .. } => "remw",
+            Remuw { .. } => "remuw",
+            Addi { .. } => "addi",
+            Slti { .. } => "slti",
+            Sltiu { .. } => "sltiu",
+            Xori { .. } => "xori",
+            Ori { .. } => "ori",
+            Andi { .. } => "andi",
+            Slli { .. } => "slli",
+            Srli { .. } => "srli",
+            Srai { .. } => "srai",
+            Addiw { .. } => "addiw",
+            Slliw { .. } => "slliw",
+            Srliw { .. } => "srliw",
+            Sraiw { .. } => "sraiw",
+            Sb { .. } => "sb",
+            Sh { .. } => "sh",
+            Sw { .. } => "sw",
+            Sd { .. } => "sd",
+            Lb { .. } => "lb",
+            Lh { .. } => "lh",
+            Lw { .. } => "lw",
+            Lbu { .. } => "lbu",
+            Lhu { .. } => "lhu",
+            Lwu { .. } => "lwu",
+            Ld { .. } => "ld",
+            Beq { .. } => "beq",
+            Bne { .. } => "bne",
+            Blt { .. } => "blt",
+            Bltu { .. } => "bltu",
+            Bge { .. } => "bge",
+            Bgeu { .. } => "bgeu",
+            Auipc { .. } => "auipc",
+            Lui { .. } => "lui",
+            Jalr { .. } => "jalr",
+            Jal { .. } => "jal",
+            Amoswapw { .. } => "amoswap.w",
+            Amoaddw { .. } => "amoadd.w",
+            Amoxorw { .. } => "amoxor.w",
+            Amoandw { .. } => "amoand.w",
+            Amoorw { .. } => "amoor.w",
+            Amominw { .. } => "amomin.w",
+            Amomaxw { .. } => "amomax.w",
+            Amominuw { .. } => "amominu.w",
+            Amomaxuw { .. } => "amomaxu.w",
+            Ecall => "ecall",
+            Ebreak => "ebreak",
+            Uret => "uret",
+            Sret => "sret",
+            Wfi => "wfi",
+            Mret => "mret",
+            Mnret => "mnret",
+            Dret => "dret",
+            Csrrw { .. } => "csrrw",
+            Csrrs { .. } => "csrrs",
+            Csrrc { .. } => "csrrc",
+            Csrrwi { .. } => "csrrwi",
+            Csrrsi { .. } => "csrrsi",
+            Csrrci { .. } => "csrrci",
+            FenceI => "fence.i",
+            Fence { .. } => "fence",
+            FenceTso => "fence.tso",
+            Long { .. } => ".insn",
+        }
+    }
+
+    /// Returns the extension that defines this instruction.
+    pub fn extension(&self) -> Extension {
+        match self {
+            Mul { .. } | Mulh { .. } | Mulhsu { .. } | Mulhu { .. } | Div { .. } | Divu { .. } | Rem { .. } | Remu { .. } |
+            Mulw { .. } | Divw { .. } | Divuw { .. } | Remw { .. } | Remuw { .. } => Extension::M,
+
+            Amoswapw { .. } | Amoaddw { .. } | Amoxorw { .. } | Amoandw { .. } | Amoorw { .. } |
+            Amominw { .. } | Amomaxw { .. } | Amominuw { .. } | Amomaxuw { .. } => Extension::A,
+
+            Csrrw { .. } | Csrrs { .. } | Csrrc { .. } | Csrrwi { .. } | Csrrsi { .. } | Csrrci { .. } => Extension::Zicsr,
+            FenceI => Extension::Zifencei,
+
+            Uret => Extension::N,
+            Sret => Extension::S,
+            Mnret => Extension::Smrnmi,
+            Dret => Extension::Sdext,
+            Long { .. } => Extension::Custom,
+            _ => Extension::I,
+        }
+    }
+
+    /// Returns true for instructions that only exist when XLEN is 64.
+    pub fn is_rv64_only(&self) -> bool {
+        match self {
+            Ld { .. } | Sd { .. } | Lwu { .. } |
+            Addiw { .. } | Slliw { .. } | Srliw { .. } | Sraiw { .. } |
+            Addw { .. } | Subw { .. } | Sllw { .. } | Srlw { .. } | Sraw { .. } |
+            Mulw { .. } | Divw { .. } | Divuw { .. } | Remw { .. } | Remuw { .. } => true,
+            Slli { shamt, .. } | Srli { shamt, .. } | Srai { shamt, .. } => *shamt >= 32,
+            _ => false,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,127 @@
+use crate::csr::CsrRegister;
+use crate::error::{DecodeError, DecodeErrorKind};
+use crate::format::{FormatOptions, Plain, Token};
+use crate::instruction::InstructionFormat;
+use crate::register::Register;
+use crate::stream::StreamItem;
+
+/// Quotes and escapes `s` as a JSON string.
+fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn register(reg: Register) -> String {
+    format!("{{\"kind\":\"register\",\"name\":{},\"number\":{}}}", string(reg.abi_name()), usize::from(reg))
+}
+
+/// The operand tokens as objects tagged with their `kind`, separators are dropped.
+fn operands(tokens: &[Token]) -> String {
+    let operands: Vec<String> = tokens.iter().filter_map(|token| Some(match token {
+        Token::Mnemonic(_) | Token::Separator(_) => return None,
+        Token::Register(reg) => register(*reg),
+        Token::Imm(imm) => format!("{{\"kind\":\"imm\",\"value\":{}}}", imm),
+        Token::UImm(imm) => format!("{{\"kind\":\"uimm\",\"value\":\"{:x}\"}}", imm),
+        Token::Address(address) => format!("{{\"kind\":\"address\",\"value\":\"{:x}\"}}", address),
+        Token::Csr(csr) => {
+            let number = match csr {
+                CsrRegister::Invalid(number) => *number as usize,
+                csr => usize::from(*csr),
+            };
+            format!("{{\"kind\":\"csr\",\"name\":{},\"number\":{}}}", string(&csr.name()), number)
+        }
+        Token::Memory { offset, base } => format!("{{\"kind\":\"memory\",\"offset\":{},\"base\":{}}}", offset, register(*base)),
+        Token::Text(text) => format!("{{\"kind\":\"text\",\"value\":{}}}", string(text)),
+    })).collect();
+    format!("[{}]", operands.join(","))
+}
+
+fn error(err: &DecodeError) -> String {
+    let kind = match err.kind {
+        DecodeErrorKind::Illegal => "illegal",
+        DecodeErrorKind::Reserved => "reserved",
+        DecodeErrorKind::ExtensionDisabled(_) => "extension_disabled",
+        DecodeErrorKind::Unimplemented => "unimplemented",
+        DecodeErrorKind::Truncated => "truncated",
+    };
+    let mut out = format!("{{\"kind\":\"{}\",\"message\":{}", kind, string(&err.to_string()));
+    if let DecodeErrorKind::ExtensionDisabled(ext) = err.kind {
+        out += &format!(",\"extension\":\"{}\"", ext);
+    }
+    if let Some((field, value)) = err.field {
+        out += &format!(",\"field\":\"{}\",\"value\":{}", format!("{:?}", field).to_lowercase(), value);
+    }
+    out + "}"
+}
+
+impl StreamItem<'_> {
+    /// Describes this item as a single-line JSON object:
+    ///
+    /// ```json
+    /// {"address":"1000","raw":"00050433","length":4,"format":"R","mnemonic":"add","compressed":null,
+    ///  "extension":"i","text":"add\ts0,a0,zero","operands":[{"kind":"register","name":"s0","number":8},...]}
+    /// ```
+    ///
+    /// `address`, `raw` and the values of `uimm` and `address` operands are hex
+    /// strings without a prefix, like objdump prints them, so 64-bit values survive
+    /// parsers that read numbers as doubles. Operands
+    /// are `register`, `imm`, `uimm`, `address`, `csr`, `memory` or `text`, following
+    /// `options` (with the PC set to this item's address). Undecodable bytes have
+    /// an `error` object with a `kind`, the `message`, and the offending `field` if known.
+    pub fn to_json(&self, options: &FormatOptions) -> String {
+        let raw: String = self.bytes.iter().rev().map(|b| format!("{:02x}", b)).collect();
+        let mut first = [0u8; 4];
+        first[..self.len.min(4)].copy_from_slice(&self.bytes[..self.len.min(4)]);
+        let mut out = format!(
+            "{{\"address\":\"{:x}\",\"raw\":\"{}\",\"length\":{},\"format\":\"{}\"",
+            self.address, raw, self.len, InstructionFormat::of(u32::from_le_bytes(first)),
+        );
+        match &self.instruction {
+            Ok(inst) => {
+                let compressed = self.compressed();
+                let options = options.with_pc(self.address).with_compressed(compressed);
+                let tokens = inst.tokens_with(&options);
+                let mnemonic = match tokens.first() {
+                    Some(Token::Mnemonic(name)) => name.clone(),
+                    _ => inst.mnemonic().to_string(),
+                };
+                out += &format!(
+                    ",\"mnemonic\":{},\"compressed\":{},\"extension\":\"{}\",\"text\":{},\"operands\":{}}}",
+                    string(&mnemonic),
+                    compressed.map_or("null".to_string(), string),
+                    inst.extension(),
+                    string(&inst.display_with(&options, &Plain).to_string()),
+                    operands(&tokens),
+                );
+            }
+            Err(err) => out += &format!(",\"error\":{}}}", error(err)),
+        }
+        out
+    }
+}
+
+/// A JSON array of [`StreamItem::to_json`] objects, one per line.
+pub fn json_array<'a>(items: impl IntoIterator<Item = StreamItem<'a>>, options: &FormatOptions) -> String {
+    let items: Vec<String> = items.into_iter().map(|item| item.to_json(options)).collect();
+    match items.is_empty() {
+        true => "[]\n".to_string(),
+        false => format!("[\n{}\n]\n", items.join(",\n")),
+    }
+}
+
+/// JSON lines: one [`StreamItem::to_json`] object per line.
+pub fn json_lines<'a>(items: impl IntoIterator<Item = StreamItem<'a>>, options: &FormatOptions) -> String {
+    items.into_iter().map(|item| item.to_json(options) + "\n").collect()
+}
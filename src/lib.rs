@@ -5,20 +5,68 @@ mod decoder;
 mod optype;
 mod opcode;
 mod compressed;
+mod error;
+mod encoder;
+mod stream;
+mod fetch;
+mod parser;
+mod assembler;
+mod format;
+mod listing;
+mod elf;
+mod image;
+mod json;
+mod explain;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod extension;
 
-pub use decoder::decode;
-pub use instruction::Instruction;
+pub use decoder::{decode, decode_bytes, decode_full, decode_full_bytes, Decoder};
+pub use instruction::{DecodedInstruction, FenceFlags, Instruction, InstructionFormat};
 pub use register::Register;
-pub use csr::CsrRegister;
+pub use csr::{CsrRegister, IndirectCsr};
+pub use error::{AsmError, AsmErrorKind, DecodeError, DecodeErrorKind, ElfError, EncodeError, Field, ImageError, ImageErrorKind, ParseError};
+pub use extension::Extension;
+pub use stream::{InstructionStream, StreamItem};
+pub use fetch::{decode_fetch, Fetch, FetchError};
+pub use assembler::assemble;
+pub use format::{Ansi, FormatOptions, Plain, Render, Rendered, Syntax, Token};
+pub use listing::{Listing, ListingLine};
+pub use elf::{Elf, Section, Symbol};
+pub use image::{Image, Segment};
+pub use json::{json_array, json_lines};
+pub use explain::{explain, BitField, Explanation};
 
-pub fn opcode_size(full_opcode: u32) -> usize {
-    if full_opcode&0b11 == 0b11 {
-        4
+/// Longest instruction the length encoding can describe (176 bits).
+pub const MAX_OPCODE_SIZE: usize = 22;
+
+/// Returns the length in bytes of the instruction starting with `first_parcel`,
+/// or `None` for the reserved >=192-bit encoding.
+pub fn instruction_length(first_parcel: u16) -> Option<usize> {
+    if first_parcel&0b11 != 0b11 {
+        Some(2)
+    } else if first_parcel&0b11100 != 0b11100 {
+        Some(4)
+    } else if first_parcel&0b111111 == 0b011111 {
+        Some(6)
+    } else if first_parcel&0b1111111 == 0b0111111 {
+        Some(8)
     } else {
-        2
+        // xxxx xnnn x111 1111: 80 + 16*nnn bits, nnn == 0b111 is reserved for >=192-bit instructions
+        match (first_parcel>>12)&0b111 {
+            0b111 => None,
+            nnn => Some(10 + 2*nnn as usize),
+        }
     }
 }
 
+/// Returns the length in bytes of the instruction starting at `full_opcode`.
+/// Only the low 16 bits are looked at. The reserved >=192-bit encoding reports
+/// a single parcel so stream decoders can skip it and resynchronize.
+pub fn opcode_size(full_opcode: u32) -> usize {
+    instruction_length(full_opcode as u16).unwrap_or(2)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,13 +161,958 @@ mod tests {
     #[test]
     fn fencei() {
         assert_eq!(
-            decode(0xFFF0100f),
+            decode(0xFFF0100F),
             Ok(Instruction::FenceI)
         );
         assert_eq!(
             decode(0x0000100f),
             Ok(Instruction::FenceI)
         );
+        // rd and rs1 are ignored
+        assert_eq!(
+            decode(0x0335850f),
+            Ok(Instruction::Fence { pred: 0b0011.into(), succ: 0b0011.into() })
+        );
+    }
+
+    #[test]
+    fn fence_tso() {
+        assert_eq!(decode(0x8330000f), Ok(Instruction::FenceTso));
+        assert_eq!(Instruction::FenceTso.encode(), Ok(0x8330000f));
+        assert_eq!(Instruction::FenceTso.display_with(&FormatOptions::objdump(), &Plain).to_string(), "fence.tso");
+        assert_eq!("fence.tso".parse::<Instruction>(), Ok(Instruction::FenceTso));
+        // Other fm values are reserved and act as a normal fence
+        assert_eq!(
+            decode(0x8310000f),
+            Ok(Instruction::Fence { pred: 0b0011.into(), succ: 0b0001.into() })
+        );
+    }
+
+    #[test]
+    fn length_encoding() {
+        assert_eq!(opcode_size(0x4501), 2);
+        assert_eq!(opcode_size(0x00050433), 4);
+        assert_eq!(opcode_size(0x001f), 6);
+        assert_eq!(opcode_size(0x005f), 6);
+        assert_eq!(opcode_size(0x003f), 8);
+        assert_eq!(opcode_size(0x007f), 10);
+        assert_eq!(opcode_size(0x107f), 12);
+        assert_eq!(opcode_size(0x607f), 22);
+        assert_eq!(instruction_length(0x707f), None);
+        // nnn == 0b111 after the 0b1111111 prefix is the only reserved length
+        for parcel in 0..=u16::MAX {
+            assert_eq!(instruction_length(parcel).is_none(), parcel & 0x707f == 0x707f, "0x{parcel:04x}");
+        }
+    }
+
+    #[test]
+    fn long_instructions() {
+        let bytes = [0x1f, 0x00, 0x11, 0x22, 0x33, 0x44, 0x13, 0x05];
+        let mut raw = [0u8; MAX_OPCODE_SIZE];
+        raw[..6].copy_from_slice(&bytes[..6]);
+        assert_eq!(decode_bytes(&bytes), Ok(Instruction::Long { len: 6, raw }));
+        assert!(decode(0x2211001f).is_err());
+        assert!(decode_bytes(&bytes[..4]).is_err());
+        assert_eq!(
+            decode_bytes(&[0x13, 0x85, 0x35, 0x12]),
+            Ok(Instruction::Addi { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval1, imm: 0x123 })
+        );
+        assert_eq!(
+            decode_bytes(&[0x01, 0x45]),
+            Ok(Instruction::Addi { rd: Register::ArgumentRetval0, rs1: Register::Zero, imm: 0 })
+        );
+    }
+
+    #[test]
+    fn system() {
+        assert_eq!(decode(0x00000073), Ok(Instruction::Ecall));
+        assert_eq!(decode(0x10200073), Ok(Instruction::Sret));
+        assert_eq!(decode(0x30200073), Ok(Instruction::Mret));
+        assert_eq!(decode(0x10500073), Ok(Instruction::Wfi));
+        assert_eq!(decode(0x70200073), Ok(Instruction::Mnret));
+        assert_eq!(decode(0x7B200073), Ok(Instruction::Dret));
+        assert!(decode(0x7B300073).is_err());
+        assert_eq!(
+            decode(0x7b2020f3),
+            Ok(Instruction::Csrrs { rd: Register::ReturnAddress, rs1: Register::Zero, csr: CsrRegister::DScratch0 })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = decode(0x0000703b).unwrap_err();
+        assert_eq!(err.kind, DecodeErrorKind::Illegal);
+        assert_eq!((err.raw, err.len, err.opcode), (0x0000703b, 4, 0b0111011));
+        assert_eq!(err.field, Some((Field::Funct3, 0b111)));
+        assert_eq!(err.to_string(), "illegal 32-bit instruction 0x0000703b: funct3 = 0x7");
+
+        assert_eq!(decode(0x02a5d51b).unwrap_err().kind, DecodeErrorKind::Reserved);
+        assert_eq!(decode(0x00002007).unwrap_err().kind, DecodeErrorKind::Unimplemented);
+        // OP-V: vadd.vv v1, v2, v3
+        assert_eq!(decode(0x022180d7).unwrap_err().kind, DecodeErrorKind::Unimplemented);
+        assert_eq!(decode(0x0000006b).unwrap_err().field, Some((Field::Opcode, 0b1101011)));
+        // sfence.vma a0, a1, sfence.vma, hfence.vvma a0, a1, hfence.gvma, sinval.vma a0, a1, sfence.inval.ir
+        for raw in [0x12b50073, 0x12000073, 0x22b50073, 0x62000073, 0x16b50073, 0x18100073] {
+            assert_eq!(decode(raw).unwrap_err().kind, DecodeErrorKind::Unimplemented);
+        }
+        assert_eq!(decode(0x120000f3).unwrap_err().field, Some((Field::Rd, 1)));
+        assert_eq!(decode(0x18200073).unwrap_err().field, Some((Field::Funct12, 0x182)));
+        assert_eq!(decode(0x00000000).unwrap_err().kind, DecodeErrorKind::Illegal);
+        assert_eq!(decode(0x00002001).unwrap_err().opcode, 0b00101);
+        // RVC HINTs decode to the instruction they expand to
+        use Register::*;
+        assert_eq!(decode(0x4001), Ok(Instruction::Addi { rd: Zero, rs1: Zero, imm: 0 }));
+        assert_eq!(decode(0x0082), Ok(Instruction::Slli { rd: ReturnAddress, rs1: ReturnAddress, shamt: 0 }));
+        assert_eq!(decode(0x0081), Ok(Instruction::Addi { rd: ReturnAddress, rs1: ReturnAddress, imm: 0 }));
+        assert_eq!(decode(0x6005), Ok(Instruction::Lui { rd: Zero, uimm: 1 }));
+        assert_eq!(decode(0x8101), Ok(Instruction::Srli { rd: ArgumentRetval0, rs1: ArgumentRetval0, shamt: 0 }));
+        assert_eq!(decode_full(0x800a).unwrap().compressed, Some("c.mv"));
+        assert_eq!(decode_full(0x900a).unwrap().compressed, Some("c.add"));
+        assert_eq!(decode(0x900a), Ok(Instruction::Add { rd: Zero, rs1: Zero, rs2: StackPointer }));
+        // c.lui with a zero immediate stays reserved
+        assert_eq!(decode(0x6001).unwrap_err().kind, DecodeErrorKind::Reserved);
+        assert_eq!(decode_bytes(&[0x13, 0x05]).unwrap_err().kind, DecodeErrorKind::Truncated);
+
+        let err = decode_full(0xffffffff).unwrap_err();
+        assert_eq!((err.kind, err.raw, err.len), (DecodeErrorKind::Reserved, 0xffffffff, 0));
+        assert_eq!(err.field, Some((Field::Funct3, 0b111)));
+        assert_eq!(err.to_string(), "reserved >=192-bit length encoding 0xffffffff: funct3 = 0x7");
+        assert_eq!(decode_bytes(&[0x7f, 0xf0]).unwrap_err().to_string(), "reserved >=192-bit length encoding 0x0000f07f: funct3 = 0x7");
+    }
+
+    #[test]
+    fn decoder_config() {
+        let decoder = Decoder::from_isa_string("rv64imafdc_zicsr_zba_zbb").unwrap();
+        assert_eq!(decoder.xlen(), 64);
+        assert!(decoder.is_enabled(Extension::Zba) && decoder.is_enabled(Extension::D));
+        assert!(!decoder.is_enabled(Extension::Zifencei));
+        assert_eq!(decoder.decode(0x02b50533), Ok(Instruction::Mul { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval0, rs2: Register::ArgumentRetval1 }));
+        assert_eq!(
+            decoder.decode(0x0000100F).unwrap_err().kind,
+            DecodeErrorKind::ExtensionDisabled(Extension::Zifencei)
+        );
+
+        let decoder = Decoder::from_isa_string("RV32I2p1_Zicsr").unwrap();
+        assert_eq!(decoder.decode(0x02b50533).unwrap_err().kind, DecodeErrorKind::ExtensionDisabled(Extension::M));
+        assert_eq!(decoder.decode(0x4501).unwrap_err().kind, DecodeErrorKind::ExtensionDisabled(Extension::C));
+        assert_eq!(decoder.decode(0xAA62BAA3).unwrap_err().kind, DecodeErrorKind::Illegal);
+        assert!(decoder.decode(0x12358513).is_ok());
+
+        let decoder = Decoder::from_isa_string("rv64gc").unwrap();
+        assert!(decoder.decode(0x0000100F).is_ok());
+        assert!(Decoder::from_isa_string("x86_64").is_err());
+
+        // RV64IMAC
+        let decoder = Decoder::from_misa(2 << 62 | 1 << 0 | 1 << 2 | 1 << 8 | 1 << 12);
+        assert_eq!(decoder.xlen(), 64);
+        assert!(decoder.is_enabled(Extension::A) && !decoder.is_enabled(Extension::S));
+        assert_eq!(decoder.decode(0x10200073).unwrap_err().kind, DecodeErrorKind::ExtensionDisabled(Extension::S));
+
+        // RV32IMC, MXL in bits 31:30
+        let decoder = Decoder::from_misa(0x4000_1104);
+        assert_eq!(decoder.xlen(), 32);
+        assert!(decoder.is_enabled(Extension::M) && !decoder.is_enabled(Extension::A));
+        assert_eq!(decoder.decode(0x00053503).unwrap_err().kind, DecodeErrorKind::Illegal);
+        assert_eq!(decoder.decode(0x02051513), Err(DecodeError::illegal(0x02051513, Field::Shamt, 32)));
+        // RVC slots that mean something else on RV32
+        let jal = decoder.decode_full(0x2001).unwrap();
+        assert_eq!((jal.instruction, jal.compressed), (Instruction::Jal { rd: Register::ReturnAddress, imm: 0 }, Some("c.jal")));
+        assert_eq!(decoder.decode_bytes(&[0xfd, 0x3f]), Ok(Instruction::Jal { rd: Register::ReturnAddress, imm: -2 }));
+        assert_eq!(decoder.decode(0x6002).unwrap_err().kind, DecodeErrorKind::Unimplemented);
+        assert_eq!(decoder.decode(0x6000).unwrap_err().kind, DecodeErrorKind::Unimplemented);
+        assert_eq!(decoder.decode(0x1502), Err(DecodeError::reserved(0x1502, Field::Shamt, 32)));
+        assert_eq!(decoder.decode(0x9101), Err(DecodeError::reserved(0x9101, Field::Shamt, 32)));
+        assert_eq!(decoder.decode(0x9d0d), Err(DecodeError::reserved(0x9d0d, Field::Funct2, 0)));
+        assert!(decoder.decode(0x0502).is_ok());
+
+        // The E base only has x0-x15
+        let decoder = Decoder::from_isa_string("rv32ec").unwrap();
+        assert!(decoder.is_embedded() && !Decoder::new().is_embedded());
+        assert!(decoder.decode(0x00f787b3).is_ok());
+        assert_eq!(decoder.decode(0x01ff8fb3).unwrap_err().field, Some((Field::Rd, 31)));
+        assert_eq!(decoder.decode(0x01050533).unwrap_err().field, Some((Field::Rs2, 16)));
+        assert_eq!(decoder.decode(0x8542), Err(DecodeError::illegal(0x8542, Field::Rs2, 16)));
+        assert!(Decoder::from_misa(0x4000_0014).is_embedded());
+    }
+
+    #[test]
+    fn decoded_instruction() {
+        let decoded = decode_full(0xdead1101).unwrap();
+        assert_eq!(decoded.instruction, Instruction::Addi { rd: Register::StackPointer, rs1: Register::StackPointer, imm: -32 });
+        assert_eq!((decoded.raw, decoded.len, decoded.compressed), (0x1101, 2, Some("c.addi")));
+        assert!(decoded.is_compressed());
+
+        let decoded = decode_full_bytes(&[0x13, 0x01, 0x01, 0xfe]).unwrap();
+        assert_eq!(decoded.instruction, Instruction::Addi { rd: Register::StackPointer, rs1: Register::StackPointer, imm: -32 });
+        assert_eq!((decoded.raw, decoded.len, decoded.compressed), (0xfe010113, 4, None));
+
+        assert_eq!(decode_full(0x4501).unwrap().compressed, Some("c.li"));
+        assert_eq!(decode_full(0x8082).unwrap().compressed, Some("c.jr"));
+        assert_eq!(decode_full(0x852e).unwrap().compressed, Some("c.mv"));
+        assert_eq!(decode_full(0x7179).unwrap().compressed, Some("c.addi16sp"));
+        assert_eq!(Decoder::from_isa_string("rv64i").unwrap().decode_full(0x1101).unwrap_err().kind, DecodeErrorKind::ExtensionDisabled(Extension::C));
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(Instruction::Addi { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval1, imm: 0x123 }.encode(), Ok(0x12358513));
+        assert_eq!(Instruction::Beq { rs1: Register::ArgumentRetval0, rs2: Register::ArgumentRetval1, imm: -2740 }.encode(), Ok(0xd4b50663));
+        assert_eq!(Instruction::Sd { rs1: Register::Temp0, rs2: Register::Temp1, imm: -1355 }.encode(), Ok(0xAA62BAA3));
+        assert_eq!(Instruction::Dret.encode(), Ok(0x7B200073));
+        assert_eq!(
+            Instruction::Addi { rd: Register::Zero, rs1: Register::Zero, imm: 2048 }.encode(),
+            Err(EncodeError::ImmOutOfRange { imm: 2048, min: -2048, max: 2047 })
+        );
+        assert_eq!(
+            Instruction::Jal { rd: Register::Zero, imm: 3 }.encode(),
+            Err(EncodeError::ImmMisaligned { imm: 3, align: 2 })
+        );
+        assert!(Instruction::Slliw { rd: Register::Zero, rs1: Register::Zero, shamt: 32 }.encode().is_err());
+        assert!(Instruction::Csrrw { rd: Register::Zero, rs1: Register::Zero, csr: CsrRegister::Invalid(0x1000) }.encode().is_err());
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let mut seed = 0x1234_5678u32;
+        for opcode in (0b11..0x80).step_by(4) {
+            for _ in 0..20_000 {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let word = (seed & !0x7F) | opcode;
+                if let Ok(inst) = decode(word) {
+                    assert_eq!(decode(inst.encode().unwrap()), Ok(inst), "0x{word:08x}");
+                }
+            }
+        }
+        for half in 0..=u16::MAX as u32 {
+            if let Ok(inst) = decode(half) {
+                assert_eq!(decode(inst.encode().unwrap()), Ok(inst), "0x{half:04x}");
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        use Register::*;
+        assert_eq!("addi a0, a1, -5".parse(), Ok(Instruction::Addi { rd: ArgumentRetval0, rs1: ArgumentRetval1, imm: -5 }));
+        assert_eq!("sd ra, 8(sp)".parse(), Ok(Instruction::Sd { rs1: StackPointer, rs2: ReturnAddress, imm: 8 }));
+        assert_eq!("LD x15, (fp)".parse(), Ok(Instruction::Ld { rd: Argument5, rs1: Saved0, imm: 0 }));
+        assert_eq!("lui t0, 0x12345".parse(), Ok(Instruction::Lui { rd: Temp0, uimm: 0x12345 }));
+        assert_eq!("beq a0, zero, -0x10".parse(), Ok(Instruction::Beq { rs1: ArgumentRetval0, rs2: Zero, imm: -16 }));
+        assert_eq!("csrrs a0, mstatus, zero".parse(), Ok(Instruction::Csrrs { rd: ArgumentRetval0, rs1: Zero, csr: CsrRegister::MStatus }));
+        assert_eq!("csrrwi x0, 0x340, 3".parse(), Ok(Instruction::Csrrwi { rd: Zero, csr: CsrRegister::MScratch, imm: 3 }));
+        assert_eq!("csrrs a0, hpmcounter3, zero".parse(), Ok(Instruction::Csrrs { rd: ArgumentRetval0, rs1: Zero, csr: CsrRegister::HPMCounter(3) }));
+        assert_eq!("amoadd.w.aqrl a0, a1, (a2)".parse(), Ok(Instruction::Amoaddw { rd: ArgumentRetval0, rs1: Argument2, rs2: ArgumentRetval1, aq: true, rl: true }));
+        assert_eq!("fence".parse(), Ok(Instruction::Fence { pred: 0b1111.into(), succ: 0b1111.into() }));
+        assert_eq!("fence rw, w".parse(), Ok(Instruction::Fence { pred: 0b0011.into(), succ: 0b0001.into() }));
+        assert_eq!("ret".parse(), Ok(Instruction::Jalr { rd: Zero, rs1: ReturnAddress, imm: 0 }));
+        assert_eq!("addi a0, a1, 0x12358513".parse::<Instruction>(), Err(ParseError::Encode(EncodeError::ImmOutOfRange { imm: 0x12358513, min: -2048, max: 2047 })));
+        assert_eq!("addi a0, a1".parse::<Instruction>(), Err(ParseError::OperandCount { expected: 3, found: 2 }));
+        assert_eq!("addi a0, x32, 1".parse::<Instruction>(), Err(ParseError::InvalidRegister("x32".to_string())));
+        assert_eq!("csrrw a0, mfoo, a1".parse::<Instruction>(), Err(ParseError::InvalidCsr("mfoo".to_string())));
+        assert_eq!("add.aq a0, a1, a2".parse::<Instruction>(), Err(ParseError::UnknownMnemonic("add.aq".to_string())));
+        assert_eq!("mstatus".parse(), Ok(CsrRegister::MStatus));
+        assert_eq!("UNKNOWN(0x7C0)".parse(), Ok(CsrRegister::Other(0x7C0)));
+        assert_eq!("INVALID(0x1000)".parse(), Ok(CsrRegister::Invalid(0x1000)));
+        assert_eq!("rw".parse(), Ok(FenceFlags::from(0b0011)));
+        assert_eq!("".parse(), Ok(FenceFlags::from(0)));
+
+        let mut seed = 0x8765_4321u32;
+        for opcode in (0b11..0x80).step_by(4) {
+            for _ in 0..5_000 {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                let word = (seed & !0x7F) | opcode;
+                if let Ok(inst) = decode(word) {
+                    assert_eq!(inst.to_string().parse(), Ok(inst), "0x{word:08x}");
+                }
+            }
+        }
+        let long = decode_bytes(&[0x1f, 0x10, 0x32, 0x54, 0x76, 0x98]).unwrap();
+        assert_eq!(long.to_string().parse(), Ok(long));
+    }
+
+    #[test]
+    fn assemble() {
+        let source = "
+            .org 0x1000            # start of the test program
+        _start:
+            li a0, 10
+            la a1, data
+        loop:   addi a0, a0, -1
+            bnez a0, loop
+            call func
+            j _start
+        func: ret
+            .align 3
+        data: .word 0xdeadbeef, loop
+            .half -1
+        ";
+        let binary = super::assemble(source, 0x800).unwrap();
+        assert_eq!(binary.len(), 0x800 + 50);
+        assert!(binary[..0x800].iter().all(|b| *b == 0));
+        let insts: Vec<_> = InstructionStream::new(&binary[0x800..0x800 + 36], 0x1000).map(|item| item.instruction.unwrap()).collect();
+        use Register::*;
+        assert_eq!(insts, [
+            Instruction::Addi { rd: ArgumentRetval0, rs1: Zero, imm: 10 },
+            Instruction::Auipc { rd: ArgumentRetval1, imm: 0 },
+            Instruction::Addi { rd: ArgumentRetval1, rs1: ArgumentRetval1, imm: 36 },
+            Instruction::Addi { rd: ArgumentRetval0, rs1: ArgumentRetval0, imm: -1 },
+            Instruction::Bne { rs1: ArgumentRetval0, rs2: Zero, imm: -4 },
+            Instruction::Auipc { rd: ReturnAddress, imm: 0 },
+            Instruction::Jalr { rd: ReturnAddress, rs1: ReturnAddress, imm: 12 },
+            Instruction::Jal { rd: Zero, imm: -28 },
+            Instruction::Jalr { rd: Zero, rs1: ReturnAddress, imm: 0 },
+        ]);
+        assert_eq!(binary[0x800 + 36..], [0, 0, 0, 0, 0xef, 0xbe, 0xad, 0xde, 0x0c, 0x10, 0x00, 0x00, 0xff, 0xff]);
+
+        assert_eq!(super::assemble("j nowhere", 0), Err(AsmError { line: 1, kind: AsmErrorKind::UndefinedLabel("nowhere".to_string()) }));
+        assert_eq!(super::assemble("a: nop\na: nop", 0), Err(AsmError { line: 2, kind: AsmErrorKind::DuplicateLabel("a".to_string()) }));
+        assert_eq!(super::assemble("nop\n.org 0", 0), Err(AsmError { line: 2, kind: AsmErrorKind::OrgBackwards { org: 0, address: 4 } }));
+        assert_eq!(super::assemble(".org -1", 0), Err(AsmError { line: 1, kind: AsmErrorKind::Parse(ParseError::InvalidImm("-1".to_string())) }));
+        assert_eq!(
+            super::assemble(".org 0xfffffffffffffff0", 0),
+            Err(AsmError { line: 1, kind: AsmErrorKind::FillTooLarge { len: 0xffff_ffff_ffff_fff0 } })
+        );
+        assert_eq!(super::assemble(".align 4", u64::MAX - 2), Err(AsmError { line: 1, kind: AsmErrorKind::AddressOverflow }));
+        assert_eq!(super::assemble("nop\nnop", u64::MAX - 3), Err(AsmError { line: 1, kind: AsmErrorKind::AddressOverflow }));
+        assert_eq!(super::assemble(".org 0xffffffff80000004\nnop", 0xffff_ffff_8000_0000), Ok(vec![0, 0, 0, 0, 0x13, 0, 0, 0]));
+        assert!(matches!(super::assemble("li a0", 0), Err(AsmError { line: 1, kind: AsmErrorKind::Parse(ParseError::OperandCount { .. }) })));
+        assert!(super::assemble(".half 0x10000", 0).is_err());
+        assert!(matches!(
+            super::assemble(".word lbl\nlbl: nop", 0x1_0000_0000),
+            Err(AsmError { line: 1, kind: AsmErrorKind::Parse(ParseError::Encode(EncodeError::ImmOutOfRange { imm: 0x1_0000_0004, .. })) })
+        ));
+        assert_eq!(super::assemble(".dword lbl\nlbl: nop", 0x1_0000_0000).unwrap()[..8], 0x1_0000_0008u64.to_le_bytes());
+        assert!(super::assemble("a0: nop", 0).is_err());
+
+        // run every li expansion on a tiny interpreter
+        let mut seed = 0x0123_4567_89ab_cdefu64;
+        let mut values = vec![0, -1, 2047, -2048, 0x7fff_ffff, -0x8000_0000, 0x8000_0000, i64::MIN, i64::MAX, 0x1234_5678_9abc_def0];
+        for _ in 0..2000 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            values.push(seed as i64 >> (seed % 60));
+        }
+        for value in values {
+            let binary = super::assemble(&format!("li t0, {}", value), 0).unwrap();
+            assert!(binary.len() <= 32, "{value:#x}");
+            let mut t0 = 0i64;
+            for item in InstructionStream::new(&binary, 0) {
+                t0 = match item.instruction.unwrap() {
+                    Instruction::Lui { uimm, .. } => (uimm << 12) as i32 as i64,
+                    Instruction::Addiw { imm, .. } => t0.wrapping_add(imm) as i32 as i64,
+                    Instruction::Addi { rs1: Zero, imm, .. } => imm,
+                    Instruction::Addi { imm, .. } => t0.wrapping_add(imm),
+                    Instruction::Slli { shamt, .. } => t0 << shamt,
+                    inst => panic!("unexpected {inst:?}"),
+                };
+            }
+            assert_eq!(t0, value, "{value:#x}");
+        }
+    }
+
+    #[test]
+    fn register_names() {
+        assert_eq!("a0".parse(), Ok(Register::ArgumentRetval0));
+        assert_eq!("X10".parse(), Ok(Register::ArgumentRetval0));
+        assert_eq!("fp".parse(), Ok(Register::Saved0));
+        assert_eq!(" zero ".parse(), Ok(Register::Zero));
+        assert_eq!("x32".parse::<Register>(), Err(ParseError::InvalidRegister("x32".to_string())));
+        assert_eq!("x01".parse::<Register>(), Err(ParseError::InvalidRegister("x01".to_string())));
+        for num in 0..32 {
+            let reg = Register::from(num);
+            assert_eq!(reg.abi_name().parse(), Ok(reg));
+            assert_eq!(reg.numeric_name().parse(), Ok(reg));
+        }
+        assert_eq!(Register::Saved0.abi_name(), "s0");
+        assert_eq!(Register::Temp6.numeric_name(), "x31");
+        assert_eq!(Register::Temp6.to_string(), "t6/x31");
+    }
+
+    #[test]
+    fn format() {
+        let addi = decode(0x12358513).unwrap();
+        assert_eq!(addi.tokens(), [
+            Token::Mnemonic("addi".to_string()),
+            Token::Separator(" "),
+            Token::Register(Register::ArgumentRetval0),
+            Token::Separator(", "),
+            Token::Register(Register::ArgumentRetval1),
+            Token::Separator(", "),
+            Token::Imm(0x123),
+        ]);
+        assert_eq!(addi.display(&Plain).to_string(), "addi a0/x10, a1/x11, 291(0x123)");
+        assert_eq!(addi.to_string(), addi.display(&Plain).to_string());
+        assert_eq!(addi.display(&Ansi).to_string(), "\x1B[38;5;196maddi\x1B[0m \x1B[32ma0/x10\x1B[0m, \x1B[32ma1/x11\x1B[0m, \x1B[94m291(0x123)\x1B[0m");
+
+        let sd: Instruction = "sd ra, -8(sp)".parse().unwrap();
+        assert_eq!(sd.display(&Plain).to_string(), "sd ra/x1, -8(0xFFFFFFFFFFFFFFF8)(sp/x2)");
+        let amo: Instruction = "amoor.w.aq a0, a1, (a2)".parse().unwrap();
+        assert_eq!(amo.display(&Plain).to_string(), "amoor.w a0/x10, a1/x11, (a2/x12) (aq:true rq:false)");
+        let csr: Instruction = "csrrs t0, mepc, a1".parse().unwrap();
+        assert_eq!(csr.display(&Plain).to_string(), "csrrs t0/x5, mepc, a1/x11");
+
+        let tagged = |out: &mut dyn std::fmt::Write, token: &Token, text: &str| match token {
+            Token::Register(reg) => write!(out, "<{}>", reg.abi_name()),
+            Token::Memory { offset, base } => write!(out, "[{} + {}]", base.abi_name(), offset),
+            _ => out.write_str(text),
+        };
+        assert_eq!(sd.display(&tagged).to_string(), "sd <ra>, [sp + -8]");
+    }
+
+    #[test]
+    fn objdump_syntax() {
+        let objdump = |source: &str, pc: Option<u64>| {
+            let options = FormatOptions { pc, ..FormatOptions::objdump().without_aliases() };
+            source.parse::<Instruction>().unwrap().display_with(&options, &Plain).to_string()
+        };
+        assert_eq!(objdump("addi a0, a1, -5", None), "addi\ta0,a1,-5");
+        assert_eq!(objdump("sd ra, 8(sp)", None), "sd\tra,8(sp)");
+        assert_eq!(objdump("lw s0, -12(fp)", None), "lw\ts0,-12(s0)");
+        assert_eq!(objdump("lui a0, 0x12345", None), "lui\ta0,0x12345");
+        assert_eq!(objdump("auipc t1, -1", None), "auipc\tt1,0xfffff");
+        assert_eq!(objdump("slli a5, a5, 32", None), "slli\ta5,a5,0x20");
+        assert_eq!(objdump("beq a0, zero, -16", Some(0x1000)), "beq\ta0,zero,ff0");
+        assert_eq!(objdump("beq a0, zero, -16", None), "beq\ta0,zero,-16");
+        assert_eq!(objdump("jal ra, 0x20", Some(0x1000)), "jal\tra,1020");
+        assert_eq!(objdump("ret", None), "jalr\tzero,0(ra)");
+        assert_eq!(objdump("amoswap.w.aqrl a0, a1, (a2)", None), "amoswap.w.aqrl\ta0,a1,(a2)");
+        assert_eq!(objdump("amoadd.w a0, a1, (a2)", None), "amoadd.w\ta0,a1,(a2)");
+        assert_eq!(objdump("fence rw, rw", None), "fence\trw,rw");
+        assert_eq!(objdump("csrrs a0, mstatus, zero", None), "csrrs\ta0,mstatus,zero");
+        assert_eq!(objdump("csrrs a0, 0x7c0, zero", None), "csrrs\ta0,0x7c0,zero");
+        assert_eq!(objdump("csrrwi zero, mscratch, 3", None), "csrrwi\tzero,mscratch,3");
+        assert_eq!(objdump("ebreak", None), "ebreak");
+
+        // the native syntax only changes when a PC is given
+        let beq: Instruction = "beq a0, a1, -16".parse().unwrap();
+        assert_eq!(beq.display_with(&FormatOptions::default().with_pc(0x1000), &Plain).to_string(), "beq a0/x10, a1/x11, 0xff0");
+
+        // -M no-aliases keeps the c.* mnemonics
+        let compressed = |raw: u32| {
+            let decoded = decode_full(raw).unwrap();
+            let options = FormatOptions::objdump().without_aliases().with_pc(0x100).with_compressed(decoded.compressed);
+            decoded.instruction.display_with(&options, &Plain).to_string()
+        };
+        assert_eq!(compressed(0x4529), "c.li\ta0,10");
+        assert_eq!(compressed(0x0505), "c.addi\ta0,1");
+        assert_eq!(compressed(0x0808), "c.addi4spn\ta0,sp,16");
+        assert_eq!(compressed(0x8105), "c.srli\ta0,0x1");
+        assert_eq!(compressed(0x852e), "c.mv\ta0,a1");
+        assert_eq!(compressed(0x60a2), "c.ldsp\tra,8(sp)");
+        assert_eq!(compressed(0xc111), "c.beqz\ta0,104");
+        assert_eq!(compressed(0xbff5), "c.j\tfc");
+        assert_eq!(compressed(0x8082), "c.jr\tra");
+        assert_eq!(compressed(0x0001), "c.nop");
+        // with aliases on binutils prints the expanded form
+        let li = decode_full(0x4529).unwrap();
+        assert_eq!(li.instruction.display_with(&FormatOptions::objdump().with_compressed(li.compressed), &Plain).to_string(), "li\ta0,10");
+        let mv = decode_full(0x852e).unwrap();
+        assert_eq!(mv.instruction.display_with(&FormatOptions::objdump().with_compressed(mv.compressed), &Plain).to_string(), "mv\ta0,a1");
+    }
+
+    #[test]
+    fn aliases() {
+        let objdump = |source: &str| source.parse::<Instruction>().unwrap().display_with(&FormatOptions::objdump().with_pc(0x100), &Plain).to_string();
+        assert_eq!(objdump("addi zero, zero, 0"), "nop");
+        assert_eq!(objdump("addi a0, zero, 5"), "li\ta0,5");
+        assert_eq!(objdump("addi a0, a1, 0"), "mv\ta0,a1");
+        assert_eq!(objdump("xori a0, a1, -1"), "not\ta0,a1");
+        assert_eq!(objdump("sub a0, zero, a1"), "neg\ta0,a1");
+        assert_eq!(objdump("addiw a0, a0, 0"), "sext.w\ta0,a0");
+        assert_eq!(objdump("sltiu a0, a1, 1"), "seqz\ta0,a1");
+        assert_eq!(objdump("sltu a0, zero, a1"), "snez\ta0,a1");
+        assert_eq!(objdump("beq a0, zero, 8"), "beqz\ta0,108");
+        assert_eq!(objdump("bne a0, zero, -8"), "bnez\ta0,f8");
+        assert_eq!(objdump("blt zero, a0, 8"), "bgtz\ta0,108");
+        assert_eq!(objdump("jal zero, 0x20"), "j\t120");
+        assert_eq!(objdump("jal ra, 0x20"), "jal\t120");
+        assert_eq!(objdump("jal t0, 0x20"), "jal\tt0,120");
+        assert_eq!(objdump("jalr zero, 0(ra)"), "ret");
+        assert_eq!(objdump("jalr zero, 0(a5)"), "jr\ta5");
+        assert_eq!(objdump("jalr ra, 0(a5)"), "jalr\ta5");
+        assert_eq!(objdump("jalr ra, 8(a5)"), "jalr\t8(a5)");
+        assert_eq!(objdump("jalr zero, 8(a5)"), "jr\t8(a5)");
+        assert_eq!(objdump("jalr t0, 8(a5)"), "jalr\tt0,8(a5)");
+        assert_eq!(objdump("add a0, zero, a1"), "add\ta0,zero,a1");
+        assert_eq!(objdump("csrrs a0, mstatus, zero"), "csrr\ta0,mstatus");
+        assert_eq!(objdump("csrrw zero, mtvec, a0"), "csrw\tmtvec,a0");
+        assert_eq!(objdump("csrrs zero, mie, a0"), "csrs\tmie,a0");
+        assert_eq!(objdump("csrrci zero, mstatus, 8"), "csrci\tmstatus,8");
+        assert_eq!(objdump("csrrs a0, cycle, zero"), "rdcycle\ta0");
+        assert_eq!(objdump("csrrs a0, fflags, zero"), "frflags\ta0");
+        assert_eq!(objdump("csrrw zero, frm, a1"), "fsrm\ta1");
+        assert_eq!(objdump("csrrw a0, fcsr, a1"), "fscsr\ta0,a1");
+        assert_eq!(objdump("csrrw a0, mscratch, a1"), "csrrw\ta0,mscratch,a1");
+        assert_eq!(objdump("csrrw zero, cycle, zero"), "unimp");
+        assert_eq!(objdump("fence iorw, iorw"), "fence");
+        assert_eq!(objdump("fence rw, rw"), "fence\trw,rw");
+
+        // Display uses aliases too, and they parse back
+        let li = decode(0x00500513).unwrap();
+        assert_eq!(li.display(&Plain).to_string(), "li a0/x10, 5(0x5)");
+        assert_eq!(li.display_with(&FormatOptions::default().without_aliases(), &Plain).to_string(), "addi a0/x10, zero/x0, 5(0x5)");
+        for source in ["nop", "li a0, 5", "mv a0, a1", "bgt a0, a1, 8", "ble a0, a1, 8", "jr t0", "jr 8(t0)", "jalr -4(a5)", "csrw mepc, a0", "fsflags a0", "fsflagsi t0, 3", "rdtime s1", "pause"] {
+            let inst: Instruction = source.parse().unwrap();
+            assert_eq!(inst.to_string().parse(), Ok(inst), "{source}");
+        }
+        assert_eq!("bgt a0, a1, 8".parse(), Ok(Instruction::Blt { rs1: Register::ArgumentRetval1, rs2: Register::ArgumentRetval0, imm: 8 }));
+    }
+
+    #[test]
+    fn compress() {
+        let inst = Instruction::Addi { rd: Register::StackPointer, rs1: Register::StackPointer, imm: -32 };
+        assert_eq!(inst.compress(), Some(0x1101));
+        let inst = Instruction::Addi { rd: Register::StackPointer, rs1: Register::StackPointer, imm: -64 };
+        assert_eq!(decode(inst.compress().unwrap() as u32), Ok(inst));
+        assert_eq!(Instruction::Jalr { rd: Register::Zero, rs1: Register::ReturnAddress, imm: 0 }.compress(), Some(0x8082));
+        assert_eq!(Instruction::Addi { rd: Register::Zero, rs1: Register::Zero, imm: 0 }.compress(), Some(0x0001));
+        // x16 has no 3-bit RVC number
+        assert_eq!(Instruction::Ld { rd: Register::Argument6, rs1: Register::Saved0, imm: 8 }.compress(), None);
+        assert_eq!(Instruction::Ld { rd: Register::Argument5, rs1: Register::Saved0, imm: 8 }.compress(), Some(0x641c));
+        assert_eq!(Instruction::Ld { rd: Register::Argument5, rs1: Register::Saved0, imm: 4 }.compress(), None);
+        assert_eq!(Instruction::Addi { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval0, imm: 0 }.compress(), None);
+        assert_eq!(Instruction::Sd { rs1: Register::StackPointer, rs2: Register::ReturnAddress, imm: 8 }.compress(), Some(0xe406));
+        assert_eq!(Instruction::Lw { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval0, imm: 4 }.compress(), Some(0x4148));
+        assert_eq!(Instruction::Srli { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval0, shamt: 1 }.compress(), Some(0x8105));
+        assert_eq!(Instruction::Andi { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval0, imm: 15 }.compress(), Some(0x893d));
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        for half in (0..=u16::MAX).filter(|half| half & 0b11 != 0b11) {
+            if let Ok(inst) = decode(half as u32) {
+                // HINTs decode, but compress never produces them
+                let hint = match inst {
+                    Instruction::Addi { rd, rs1, imm } => rd == Register::Zero && imm != 0 || rd != Register::Zero && rd == rs1 && imm == 0,
+                    Instruction::Lui { rd, .. } | Instruction::Add { rd, .. } => rd == Register::Zero,
+                    Instruction::Slli { rd, shamt, .. } => rd == Register::Zero || shamt == 0,
+                    Instruction::Srli { shamt, .. } | Instruction::Srai { shamt, .. } => shamt == 0,
+                    _ => false,
+                };
+                match inst.compress() {
+                    Some(compressed) => assert_eq!(decode(compressed as u32), Ok(inst), "0x{half:04x}"),
+                    None => assert!(hint, "0x{half:04x} {inst:?}"),
+                }
+            }
+        }
+        let mut seed = 0x8765_4321u32;
+        for _ in 0..500_000 {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            if let Ok(inst) = decode(seed | 0b11) {
+                if let Some(compressed) = inst.compress() {
+                    assert_eq!(decode(compressed as u32), Ok(inst), "0x{:08x}", seed | 0b11);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stream() {
+        // c.li a0, 0; addi a0, a1, 0x123; invalid; c.jr ra; half of a 32-bit instruction
+        let code = [0x01, 0x45, 0x13, 0x85, 0x35, 0x12, 0x3b, 0x70, 0x00, 0x00, 0x82, 0x80, 0x13];
+        let items: Vec<_> = InstructionStream::new(&code, 0x8000_0000).collect();
+        assert_eq!(items.len(), 5);
+        assert_eq!(items.iter().map(|item| item.address).collect::<Vec<_>>(), [0x8000_0000, 0x8000_0002, 0x8000_0006, 0x8000_000a, 0x8000_000c]);
+        assert_eq!(items.iter().map(|item| item.len).collect::<Vec<_>>(), [2, 4, 4, 2, 1]);
+        assert_eq!(items[1].bytes, &code[2..6]);
+        assert_eq!(items[1].instruction, Ok(Instruction::Addi { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval1, imm: 0x123 }));
+        assert_eq!(items[2].instruction.unwrap_err().kind, DecodeErrorKind::Illegal);
+        assert_eq!(items[3].instruction, Ok(Instruction::Jalr { rd: Register::Zero, rs1: Register::ReturnAddress, imm: 0 }));
+        assert_eq!(items[4].instruction.unwrap_err().kind, DecodeErrorKind::Truncated);
+
+        let decoder = Decoder::from_isa_string("rv64i").unwrap();
+        let mut stream = InstructionStream::with_decoder(&code[..6], 0, decoder);
+        assert_eq!(stream.next().unwrap().instruction.unwrap_err().kind, DecodeErrorKind::ExtensionDisabled(Extension::C));
+        assert_eq!(stream.address(), 2);
+        assert!(stream.next().unwrap().instruction.is_ok());
+        assert_eq!(stream.next(), None);
+    }
+
+    #[test]
+    fn listing() {
+        let source = "
+            li a0, 10
+        loop:
+            addi a0, a0, -1
+            bnez a0, loop
+            call func
+            j 0
+        func:
+            ret
+        ";
+        let mut code = crate::assemble(source, 0x1000).unwrap();
+        code.extend_from_slice(&[0x01, 0x45, 0x00, 0x00, 0x13]);
+        let listing = Listing::new(&code, 0x1000);
+        let lines = listing.lines();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[1].label.as_deref(), Some(".L0"));
+        assert_eq!(lines[2].text, "bnez\ta0,1004 <.L0>");
+        assert_eq!(lines[4].comment.as_deref(), Some("# 1018 <.L2>"));
+        assert_eq!(lines[5].text, "j\t1014 <.L1>");
+        assert_eq!(lines[7].bytes, [0x01, 0x45]);
+        assert_eq!(lines[8].text, ".insn\t2, 0x0000");
+        assert_eq!(lines[9].text, ".byte\t0x13");
+        assert_eq!(lines.iter().filter(|line| line.label.is_some()).count(), 3);
+
+        let text = listing.to_string();
+        assert!(text.starts_with("    1000:\t00a00513            \tli\ta0,10\n\n0000000000001004 <.L0>:\n"));
+        assert!(text.contains("    1010:\t00c080e7            \tjalr\t12(ra) # 1018 <.L2>\n"));
+        assert!(text.contains("    101c:\t4501                \tli\ta0,0\n"));
+
+        let native = Listing::new(&code, 0x1000).with_options(FormatOptions::default()).lines();
+        assert_eq!(native[2].text, "bnez a0/x10, 0x1004 <.L0>");
+    }
+
+    #[test]
+    fn elf() {
+        /// Builds an ELF64 file with `.text` at 0x10000, an arch attribute and function symbols.
+        fn elf64(text: &[u8], arch: &str, symbols: &[(&str, u64)]) -> Vec<u8> {
+            let mut attributes = b"A".to_vec();
+            attributes.extend_from_slice(&(arch.len() as u32 + 17).to_le_bytes());
+            attributes.extend_from_slice(b"riscv\0\x01");
+            attributes.extend_from_slice(&(arch.len() as u32 + 7).to_le_bytes());
+            attributes.push(5);
+            attributes.extend_from_slice(arch.as_bytes());
+            attributes.push(0);
+
+            let (mut symtab, mut strtab) = (vec![0; 24], vec![0]);
+            for (name, value) in symbols {
+                symtab.extend_from_slice(&(strtab.len() as u32).to_le_bytes());
+                symtab.extend_from_slice(&[0x12, 0, 1, 0]);
+                symtab.extend_from_slice(&value.to_le_bytes());
+                symtab.extend_from_slice(&0u64.to_le_bytes());
+                strtab.extend_from_slice(name.as_bytes());
+                strtab.push(0);
+            }
+            let shstrtab = b"\0.text\0.riscv.attributes\0.symtab\0.strtab\0.shstrtab\0";
+            // name offset, type, flags, address, contents, link
+            type Header<'a> = (u32, u32, u64, u64, &'a [u8], u32);
+            let sections: [Header; 6] = [
+                (0, 0, 0, 0, &[], 0),
+                (1, 1, 6, 0x10000, text, 0),
+                (7, 0x7000_0003, 0, 0, &attributes, 0),
+                (25, 2, 0, 0, &symtab, 4),
+                (33, 3, 0, 0, &strtab, 0),
+                (41, 3, 0, 0, shstrtab, 0),
+            ];
+            let mut elf = vec![0u8; 64];
+            let mut headers = Vec::new();
+            for (name, kind, flags, address, data, link) in sections {
+                headers.extend_from_slice(&name.to_le_bytes());
+                headers.extend_from_slice(&kind.to_le_bytes());
+                for field in [flags, address, elf.len() as u64, data.len() as u64] {
+                    headers.extend_from_slice(&field.to_le_bytes());
+                }
+                headers.extend_from_slice(&link.to_le_bytes());
+                headers.extend_from_slice(&[0; 20]);
+                elf.extend_from_slice(data);
+            }
+            let shoff = elf.len() as u64;
+            elf.extend_from_slice(&headers);
+            elf[..7].copy_from_slice(b"\x7fELF\x02\x01\x01");
+            elf[18..20].copy_from_slice(&243u16.to_le_bytes());
+            elf[24..32].copy_from_slice(&0x10000u64.to_le_bytes());
+            elf[40..48].copy_from_slice(&shoff.to_le_bytes());
+            elf[58..64].copy_from_slice(&[64, 0, 6, 0, 5, 0]);
+            elf
+        }
+
+        let text = crate::assemble("start: mul a0, a0, a1\nbnez a0, start\ncall helper\nhelper: ret", 0x10000).unwrap();
+        let file = elf64(&text, "rv64i2p1_c2p0", &[("_start", 0x10000), ("helper", 0x10010)]);
+        let elf = Elf::parse(&file).unwrap();
+        assert_eq!((elf.xlen, elf.entry), (64, 0x10000));
+        assert_eq!(elf.arch().as_deref(), Some("rv64i2p1_c2p0"));
+        assert_eq!(elf.symbols.iter().map(|symbol| symbol.name.as_str()).collect::<Vec<_>>(), ["_start", "helper"]);
+        let section = elf.section(".text").unwrap();
+        assert!(section.is_executable());
+        assert_eq!((section.address, section.data), (0x10000, &text[..]));
+        assert!(!elf.section(".symtab").unwrap().is_executable());
+
+        // M isn't in the arch string
+        let lines = elf.listing(section).lines();
+        assert_eq!(lines[0].label.as_deref(), Some("_start"));
+        assert_eq!(lines[0].instruction.unwrap_err().kind, DecodeErrorKind::ExtensionDisabled(Extension::M));
+        assert_eq!(lines[1].text, "bnez\ta0,10000 <_start>");
+        assert_eq!(lines[3].comment.as_deref(), Some("# 10010 <helper>"));
+        assert_eq!(lines[4].label.as_deref(), Some("helper"));
+        assert!(elf.listing(section).to_string().starts_with("\n0000000000010000 <_start>:\n"));
+
+        let file = elf64(&text, "rv64i2p1_m2p0_c2p0", &[]);
+        let elf = Elf::parse(&file).unwrap();
+        assert!(elf.decoder().is_enabled(Extension::M));
+        assert_eq!(elf.listing(elf.section(".text").unwrap()).lines()[0].text, "mul\ta0,a0,a1");
+
+        assert_eq!(Elf::parse(b"MZ\x90\x00"), Err(ElfError::NotElf));
+        assert_eq!(Elf::parse(&file[..100]), Err(ElfError::Truncated));
+        let mut arm = file.clone();
+        arm[18] = 40;
+        assert_eq!(Elf::parse(&arm), Err(ElfError::UnsupportedMachine(40)));
+    }
+
+    #[test]
+    fn instruction_format() {
+        use InstructionFormat::*;
+        let cases = [
+            (0x00050433, R), (0x00a00513, I), (0x00112423, S), (0xfe051ee3, B), (0x00000097, U),
+            (0x0000006f, J), (0x02b50543, R4), (0x0000707b, I), (0x0001003f, Long), (0xffffffff, Long),
+            (0x8082, CR), (0x4501, CI), (0xc02e, CSS), (0x0040, CIW), (0x6108, CL),
+            (0xe108, CS), (0x8c09, CA), (0x8105, CB), (0xa001, CJ),
+        ];
+        for (raw, format) in cases {
+            assert_eq!(InstructionFormat::of(raw), format, "{:#x}", raw);
+        }
+        assert_eq!(decode_full(0x8c09).unwrap().instruction, Instruction::Sub { rd: Register::Saved0, rs1: Register::Saved0, rs2: Register::ArgumentRetval0 });
+        assert_eq!(decode_full(0x8c09).unwrap().format(), CA);
+        assert_eq!(format!("{:<4}|", CSS), "CSS |");
+    }
+
+    #[test]
+    fn image() {
+        // objcopy output for the same 24 bytes of code at 0x80010000
+        let ihex = ":02000004800179\n:1000000029457D157DFD97000000E780C0006F0049\n:0800100000003305B5028280F7\n:040000058001000076\n:00000001FF\n";
+        let srec = "S0090000742E73726563A7\nS3158001000029457D157DFD97000000E780C0006F00C2\nS30D8001001000003305B502828070\nS7058001000079\n";
+        let image = Image::from_ihex(ihex).unwrap();
+        assert_eq!(image.entry, Some(0x8001_0000));
+        assert_eq!(image.segments.len(), 1);
+        assert_eq!((image.segments[0].address, image.segments[0].end()), (0x8001_0000, Some(0x8001_0018)));
+        assert_eq!(Image::from_srec(srec), Ok(image.clone()));
+        assert_eq!(Image::parse(srec.as_bytes(), 0), Ok(image.clone()));
+        assert_eq!(Image::parse(ihex.as_bytes(), 0), Ok(image.clone()));
+
+        let code = &image.segments[0].data;
+        let raw = Image::parse(code, 0x8001_0000).unwrap();
+        assert_eq!((raw.segments.clone(), raw.entry), (image.segments.clone(), None));
+        assert_eq!(image.segments[0].stream().count(), 8);
+        assert_eq!(image.segments[0].listing().lines()[7].text, "ret");
+
+        // Extended segment address, out of order and contiguous records
+        let ihex = ":020000021000EC\n:020010008280EC\n:020000000145B8\n:020002000145B6\n";
+        let image = Image::from_ihex(ihex).unwrap();
+        assert_eq!(image.segments, [
+            Segment { address: 0x10000, data: vec![0x01, 0x45, 0x01, 0x45] },
+            Segment { address: 0x10010, data: vec![0x82, 0x80] },
+        ]);
+
+        let err = |line, kind| Err(ImageError { line, kind });
+        assert_eq!(Image::from_ihex("\n:020010008280ED"), err(2, ImageErrorKind::Checksum { expected: 0xEC, found: 0xED }));
+        assert_eq!(Image::from_ihex(":030010008280EC"), err(1, ImageErrorKind::Length));
+        assert_eq!(Image::from_ihex(":0200100082 80EC"), err(1, ImageErrorKind::InvalidRecord));
+        assert_eq!(Image::from_ihex(":00000006FA"), err(1, ImageErrorKind::UnknownRecordType(6)));
+        // 02 with 8 data bytes, then data at the top of the segment
+        assert_eq!(Image::from_ihex(":08000002FFFFFFFFFFFFFFFFFE\n:01FFFF0013EE"), err(1, ImageErrorKind::Length));
+        assert_eq!(Image::from_ihex(":0400000500000000F7"), Ok(Image { segments: vec![], entry: Some(0) }));
+        assert_eq!(Image::from_ihex(":020000050000F9"), err(1, ImageErrorKind::Length));
+        assert_eq!(Image::from_ihex(":020010008280EC\n:020011000145A7"), err(2, ImageErrorKind::Overlap { address: 0x11 }));
+        assert_eq!(Image::from_srec("S30780010000457DB5\nS30780010000457DB5"), err(2, ImageErrorKind::Overlap { address: 0x8001_0000 }));
+        assert_eq!(Image::from_binary(&[0; 4], u64::MAX).segments[0].end(), None);
+        assert_eq!(Image::from_srec("S405000000FA"), err(1, ImageErrorKind::UnknownRecordType(4)));
+        assert_eq!(Image::from_srec("S30D8001001000003305B502828071"), err(1, ImageErrorKind::Checksum { expected: 0x70, found: 0x71 }));
+    }
+
+    #[test]
+    fn json() {
+        // add s0, a0, zero; c.li a0, 0; invalid; sd ra, 8(sp); csrr a0, mstatus
+        let code = [0x33, 0x04, 0x05, 0x00, 0x01, 0x45, 0x7b, 0x70, 0x00, 0x00, 0x23, 0x34, 0x11, 0x00, 0x73, 0x25, 0x00, 0x30];
+        let options = FormatOptions::objdump().without_aliases();
+        let items: Vec<_> = InstructionStream::new(&code, 0x1000).collect();
+        assert_eq!(
+            items[0].to_json(&options),
+            r#"{"address":"1000","raw":"00050433","length":4,"format":"R","mnemonic":"add","compressed":null,"extension":"i","text":"add\ts0,a0,zero","operands":[{"kind":"register","name":"s0","number":8},{"kind":"register","name":"a0","number":10},{"kind":"register","name":"zero","number":0}]}"#
+        );
+        assert_eq!(
+            items[1].to_json(&options),
+            r#"{"address":"1004","raw":"4501","length":2,"format":"CI","mnemonic":"c.li","compressed":"c.li","extension":"i","text":"c.li\ta0,0","operands":[{"kind":"register","name":"a0","number":10},{"kind":"imm","value":0}]}"#
+        );
+        assert_eq!(
+            items[2].to_json(&options),
+            r#"{"address":"1006","raw":"0000707b","length":4,"format":"I","error":{"kind":"illegal","message":"illegal 32-bit instruction 0x0000707b: opcode = 0x7b","field":"opcode","value":123}}"#
+        );
+        assert!(items[3].to_json(&options).contains(r#""format":"S","mnemonic":"sd","#));
+        assert!(items[3].to_json(&options).ends_with(r#""operands":[{"kind":"register","name":"ra","number":1},{"kind":"memory","offset":8,"base":{"kind":"register","name":"sp","number":2}}]}"#));
+        assert!(items[4].to_json(&FormatOptions::objdump()).contains(r#""mnemonic":"csrr","compressed":null,"extension":"zicsr","text":"csrr\ta0,mstatus","operands":[{"kind":"register","name":"a0","number":10},{"kind":"csr","name":"mstatus","number":768}]"#));
+
+        let lines = json_lines(items.clone(), &options);
+        assert_eq!(lines.lines().count(), 5);
+        assert!(lines.lines().all(|line| line.starts_with("{\"address\":") && line.ends_with('}')));
+        let array = json_array(items, &options);
+        assert!(array.starts_with("[\n{") && array.ends_with("}\n]\n"));
+        assert_eq!(array.matches("},\n{").count(), 4);
+        assert_eq!(json_array(InstructionStream::new(&[], 0), &options), "[]\n");
+
+        // lui a0, 0x12345; j 0x100c
+        let code = [0x37, 0x55, 0x34, 0x12, 0x6f, 0x00, 0x80, 0x00];
+        let items: Vec<_> = InstructionStream::new(&code, 0x1000).collect();
+        assert!(items[0].to_json(&options).ends_with(r#""operands":[{"kind":"register","name":"a0","number":10},{"kind":"uimm","value":"12345"}]}"#));
+        assert!(items[1].to_json(&FormatOptions::objdump()).ends_with(r#""text":"j\t100c","operands":[{"kind":"address","value":"100c"}]}"#));
+
+        let mul = [0x33, 0x05, 0xb5, 0x02];
+        let disabled = InstructionStream::with_decoder(&mul, 0, Decoder::from_isa_string("rv64i").unwrap()).next().unwrap();
+        assert!(disabled.to_json(&options).ends_with(r#""error":{"kind":"extension_disabled","message":"m extension disabled for 32-bit instruction 0x02b50533","extension":"m"}}"#));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use Register::*;
+        let addi = Instruction::Addi { rd: ArgumentRetval0, rs1: Zero, imm: 5 };
+        assert_eq!(serde_json::to_string(&addi).unwrap(), r#"{"Addi":{"rd":"a0","rs1":"zero","imm":5}}"#);
+        assert_eq!(serde_json::from_str::<Instruction>(r#"{"Addi":{"rd":"x10","rs1":"x0","imm":5}}"#).unwrap(), addi);
+        let csrrs = Instruction::Csrrs { rd: ArgumentRetval0, rs1: Zero, csr: CsrRegister::Other(0x7C0) };
+        assert_eq!(serde_json::to_string(&csrrs).unwrap(), r#"{"Csrrs":{"rd":"a0","rs1":"zero","csr":"UNKNOWN(0x7C0)"}}"#);
+        assert_eq!(serde_json::to_string(&Instruction::Ecall).unwrap(), r#""Ecall""#);
+        assert_eq!(serde_json::to_string(&FenceFlags::from(0b1010)).unwrap(), r#""ir""#);
+        assert_eq!(serde_json::to_string(&CsrRegister::PMPCfg(2)).unwrap(), r#""pmpcfg2""#);
+        assert!(serde_json::from_str::<Register>(r#""x32""#).is_err());
+        assert!(serde_json::from_str::<FenceFlags>(r#""rx""#).is_err());
+
+        let mut seed = 0x1357_9bdfu32;
+        let mut insts = vec![decode_bytes(&[0x1f, 0x10, 0x32, 0x54, 0x76, 0x98]).unwrap()];
+        for _ in 0..50_000 {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            insts.extend(decode(seed).ok());
+        }
+        let json = serde_json::to_string(&insts).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Instruction>>(&json).unwrap(), insts);
+        let csrs: Vec<CsrRegister> = (0..0x1000u64).map(CsrRegister::from).chain([CsrRegister::Invalid(0x1234)]).collect();
+        assert_eq!(serde_json::from_str::<Vec<CsrRegister>>(&serde_json::to_string(&csrs).unwrap()).unwrap(), csrs);
+    }
+
+    #[test]
+    fn explain() {
+        let bne = crate::explain(0xfe051ee3);
+        let names: Vec<&str> = bne.fields.iter().map(|field| field.name).collect();
+        assert_eq!(names, ["imm[12]", "imm[10:5]", "rs2", "rs1", "funct3", "imm[4:1]", "imm[11]", "opcode"]);
+        assert_eq!((bne.len, bne.format, bne.imm), (4, InstructionFormat::B, Some(-4)));
+        assert!(bne.culprits().is_empty());
+        assert_eq!(bne.fields[3].register(), Some(Register::ArgumentRetval0));
+        assert!(bne.to_string().starts_with("0xfe051ee3: bne a0/x10, zero/x0, -4"));
+
+        let illegal = crate::explain(0x0000707b);
+        let culprits: Vec<&str> = illegal.culprits().iter().map(|field| field.name).collect();
+        assert_eq!(culprits, ["opcode"]);
+        assert!(illegal.to_string().contains("<- illegal"));
+
+        // c.addiw with rd = zero
+        let reserved = crate::explain(0x2001);
+        assert_eq!((reserved.len, reserved.format), (2, InstructionFormat::CI));
+        let culprits: Vec<&str> = reserved.culprits().iter().map(|field| field.name).collect();
+        assert_eq!(culprits, ["rd/rs1"]);
+        let culprits: Vec<&str> = crate::explain(0x8000).culprits().iter().map(|field| field.name).collect();
+        assert_eq!(culprits, ["funct3"]);
+
+        // reserved >=192-bit length encoding
+        let long = crate::explain(0xffffffff);
+        assert_eq!((long.len, long.format), (0, InstructionFormat::Long));
+        let culprits: Vec<&str> = long.culprits().iter().map(|field| field.name).collect();
+        assert_eq!(culprits, ["nnn"]);
+        assert!(long.to_string().starts_with("0xffffffff: (>=192-bit, Long format)\n"));
+
+        // RVC immediates are put together from their bit labels
+        let lwsp = crate::explain(0x4512);
+        let names: Vec<&str> = lwsp.fields.iter().map(|field| field.name).collect();
+        assert_eq!(names, ["funct3", "uimm[5]", "rd", "uimm[4:2|7:6]", "op"]);
+        for raw in (0..=0xFFFFu32).filter(|raw| raw & 0b11 != 0b11) {
+            use Instruction::*;
+            let Ok(inst) = decode(raw) else { continue };
+            let expected = match inst {
+                Addi { imm, .. } | Addiw { imm, .. } | Andi { imm, .. } | Jal { imm, .. } | Beq { imm, .. } | Bne { imm, .. } |
+                Lw { imm, .. } | Ld { imm, .. } | Sw { imm, .. } | Sd { imm, .. } => Some(imm),
+                Lui { uimm, .. } => Some((uimm << 12) as i32 as i64),
+                Slli { shamt, .. } | Srli { shamt, .. } | Srai { shamt, .. } => Some(shamt as i64),
+                _ => None,
+            };
+            assert_eq!(crate::explain(raw).imm, expected, "0x{raw:04x}");
+        }
+
+        let disabled = Decoder::from_isa_string("rv64i").unwrap().explain(0x02b50533);
+        assert_eq!(disabled.result.unwrap_err().kind, DecodeErrorKind::ExtensionDisabled(Extension::M));
+    }
+
+    #[test]
+    fn fetch() {
+        // addi a0, a1, 0x123 straddling a page boundary, c.li a0, 0 before it
+        let mut fetched = Vec::new();
+        {
+            let mut mmu = |address: u64| -> Result<u16, &'static str> {
+                fetched.push(address);
+                match address {
+                    0xffa => Ok(0x4501),
+                    0xffe => Ok(0x8513),
+                    0x1000 => Ok(0x1235),
+                    _ => Err("page fault"),
+                }
+            };
+            let decoded = decode_fetch(&mut mmu, 0xffe).unwrap();
+            assert_eq!(decoded.instruction, Instruction::Addi { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval1, imm: 0x123 });
+            assert_eq!(decoded.len, 4);
+            assert_eq!(decode_fetch(&mut mmu, 0xffa).unwrap().len, 2);
+            assert_eq!(decode_fetch(&mut mmu, 0x2000), Err(FetchError::Fault { parcel: 0, address: 0x2000, error: "page fault" }));
+        }
+        assert_eq!(fetched, [0xffe, 0x1000, 0xffa, 0x2000]);
+
+        let mut mmu = |address: u64| if address < 0x1000 { Ok(0x8513) } else { Err(()) };
+        assert_eq!(decode_fetch(&mut mmu, 0xffe), Err(FetchError::Fault { parcel: 1, address: 0x1000, error: () }));
+        let decoder = Decoder::from_isa_string("rv64i").unwrap();
+        let mut mmu = |_| Ok::<u16, ()>(0x4501);
+        assert!(matches!(decoder.decode_fetch(&mut mmu, 0), Err(FetchError::Decode(_))));
+    }
+
+    #[test]
+    fn csr_round_trip() {
+        for addr in 0..4096 {
+            assert_eq!(usize::from(CsrRegister::from(addr as u64)), addr);
+        }
+        assert_eq!(CsrRegister::from(0x180), CsrRegister::SATP);
+        assert_eq!(CsrRegister::from(0x680), CsrRegister::HGATP);
+        assert_eq!(CsrRegister::from(0x200), CsrRegister::VSStatus);
+        assert!(!CsrRegister::HGEIP.is_writable());
+        assert!(CsrRegister::HStatus.is_writable());
+        assert_eq!(CsrRegister::from(0xC00), CsrRegister::Cycle);
+        assert_eq!(CsrRegister::from(0xC1F), CsrRegister::HPMCounter(31));
+        assert_eq!(CsrRegister::from(0xB04), CsrRegister::MHPMCounter(4));
+        assert_eq!(CsrRegister::from(0x323), CsrRegister::MHPMEvent(3));
+        assert_eq!(CsrRegister::from(0x002), CsrRegister::FRM);
+        assert!(!CsrRegister::Cycle.is_writable());
+        assert!(CsrRegister::MCycle.is_writable());
+        assert_eq!(CsrRegister::from(0x7B1), CsrRegister::DPC);
+        assert_eq!(CsrRegister::from(0x744), CsrRegister::MNStatus);
+        assert_eq!(CsrRegister::from(0x35C), CsrRegister::MTopEI);
+        assert_eq!(CsrRegister::from(0x14D), CsrRegister::STimeCmp);
+        assert!(!CsrRegister::MTopI.is_writable());
+        assert_eq!(CsrRegister::VSIReg.indirect_select(), Some(CsrRegister::VSISelect));
+    }
+
+    #[test]
+    fn indirect_csr() {
+        for select in 0..0x200u64 {
+            assert_eq!(u64::from(IndirectCsr::from(select)), select);
+        }
+        assert_eq!(IndirectCsr::from(0x70), IndirectCsr::EIDelivery);
+        assert_eq!(IndirectCsr::from(0x82), IndirectCsr::EIP(2));
+        assert!(!IndirectCsr::EIE(3).exists_in_rv64());
+        assert_eq!(IndirectCsr::from(1 << 63), IndirectCsr::Custom(1 << 63));
+        assert_eq!(IndirectCsr::EIP(2).to_string(), "eip2");
+        assert_eq!(CsrRegister::MStatus.to_string(), "mstatus");
+    }
+
+    #[test]
+    fn compressed_addi() {
+        // imm[5] comes from bit 12, the rd bits below it must not leak into imm[4:0]
+        assert_eq!(decode(0x0505), Ok(Instruction::Addi { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval0, imm: 1 }));
+        assert_eq!(decode(0x07fd), Ok(Instruction::Addi { rd: Register::Argument5, rs1: Register::Argument5, imm: 31 }));
+        assert_eq!(decode(0x1101), Ok(Instruction::Addi { rd: Register::StackPointer, rs1: Register::StackPointer, imm: -32 }));
+        assert_eq!(decode(0x249d), Ok(Instruction::Addiw { rd: Register::Saved1, rs1: Register::Saved1, imm: 7 }));
+        assert_eq!(decode(0x357d), Ok(Instruction::Addiw { rd: Register::ArgumentRetval0, rs1: Register::ArgumentRetval0, imm: -1 }));
+    }
+
+    #[test]
+    fn slli_amo_and_c_lui() {
+        use Register::*;
+        assert_eq!(decode(0x03f51513), Ok(Instruction::Slli { rd: ArgumentRetval0, rs1: ArgumentRetval0, shamt: 63 }));
+        assert_eq!(decode(0x40051513).unwrap_err().field, Some((Field::Funct6, 0b010000)));
+        assert_eq!(decode(0x20b6252f), Ok(Instruction::Amoxorw { rd: ArgumentRetval0, rs1: Argument2, rs2: ArgumentRetval1, aq: false, rl: false }));
+        assert_eq!(decode(0xe6b6252f), Ok(Instruction::Amomaxuw { rd: ArgumentRetval0, rs1: Argument2, rs2: ArgumentRetval1, aq: true, rl: true }));
+        // c.lui a0, 0xfffff: the sign-extended imm stays a 20-bit uimm
+        assert_eq!(decode(0x757d), Ok(Instruction::Lui { rd: ArgumentRetval0, uimm: 0xfffff }));
     }
 
     #[test]
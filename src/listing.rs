@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use crate::decoder::Decoder;
+use crate::error::{DecodeError, DecodeErrorKind};
+use crate::format::{FormatOptions, Plain, Rendered, Token};
+use crate::instruction::Instruction;
+use crate::instruction::Instruction::*;
+use crate::register::Register;
+use crate::stream::{InstructionStream, StreamItem};
+
+/// One instruction of a [`Listing`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine<'a> {
+    pub address: u64,
+    pub bytes: &'a [u8],
+    /// Symbol at this address, or a synthesized local label when a branch or
+    /// jump in the buffer lands here.
+    pub label: Option<String>,
+    pub instruction: Result<Instruction, DecodeError>,
+    /// Mnemonic and operands, targets inside the buffer are followed by `<label>`.
+    pub text: String,
+    /// Address an `auipc` pair ending with this instruction refers to, e.g. `# 1028 <.L2>`.
+    pub comment: Option<String>,
+}
+
+/// objdump-style disassembly of a code buffer.
+#[derive(Debug, Clone)]
+pub struct Listing<'a> {
+    bytes: &'a [u8],
+    base: u64,
+    decoder: Decoder,
+    options: FormatOptions,
+    symbols: BTreeMap<u64, String>,
+}
+
+/// Target of a branch or direct jump at `pc`.
+fn jump_target(inst: &Instruction, pc: u64) -> Option<u64> {
+    match *inst {
+        Beq { imm, .. } | Bne { imm, .. } | Blt { imm, .. } | Bltu { imm, .. } |
+        Bge { imm, .. } | Bgeu { imm, .. } | Jal { imm, .. } => Some(pc.wrapping_add(imm as u64)),
+        _ => None,
+    }
+}
+
+/// The base register and offset of instructions that can complete an `auipc` pair.
+fn pair_operand(inst: &Instruction) -> Option<(Register, i64)> {
+    match *inst {
+        Addi { rs1, imm, .. } | Jalr { rs1, imm, .. } |
+        Lb { rs1, imm, .. } | Lh { rs1, imm, .. } | Lw { rs1, imm, .. } | Ld { rs1, imm, .. } |
+        Lbu { rs1, imm, .. } | Lhu { rs1, imm, .. } | Lwu { rs1, imm, .. } |
+        Sb { rs1, imm, .. } | Sh { rs1, imm, .. } | Sw { rs1, imm, .. } | Sd { rs1, imm, .. } => Some((rs1, imm)),
+        _ => None,
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().rev().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl<'a> Listing<'a> {
+    /// Lists `bytes` loaded at `base` as RV64 with every extension, in objdump syntax.
+    pub fn new(bytes: &'a [u8], base: u64) -> Self {
+        Self { bytes, base, decoder: Decoder::new(), options: FormatOptions::objdump(), symbols: BTreeMap::new() }
+    }
+
+    pub fn with_decoder(self, decoder: Decoder) -> Self {
+        Self { decoder, ..self }
+    }
+
+    /// Changes the syntax and aliasing, the PC is set per instruction.
+    pub fn with_options(self, options: FormatOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Names addresses, e.g. from an ELF symbol table. Targets without a symbol
+    /// still get a local label, and the first name given for an address wins.
+    pub fn with_symbols(mut self, symbols: impl IntoIterator<Item = (u64, String)>) -> Self {
+        for (address, name) in symbols {
+            self.symbols.entry(address).or_insert(name);
+        }
+        self
+    }
+
+    pub fn lines(&self) -> Vec<ListingLine<'a>> {
+        let items: Vec<StreamItem<'a>> = InstructionStream::with_decoder(self.bytes, self.base, self.decoder).collect();
+        let boundaries: BTreeSet<u64> = items.iter().map(|item| item.address).collect();
+
+        // auipc pairs, `auipc rd` followed by an instruction using `rd` as its base
+        let mut pairs = vec![None; items.len()];
+        for (i, window) in items.windows(2).enumerate() {
+            if let (Ok(Auipc { rd, imm }), Ok(next)) = (&window[0].instruction, &window[1].instruction) {
+                if let Some((_, offset)) = pair_operand(next).filter(|(rs1, _)| rs1 == rd && *rd != Register::Zero) {
+                    pairs[i + 1] = Some(window[0].address.wrapping_add((*imm << 12) as u64).wrapping_add(offset as u64));
+                }
+            }
+        }
+
+        let mut targets = BTreeSet::new();
+        for (item, pair) in items.iter().zip(&pairs) {
+            if let Ok(inst) = &item.instruction {
+                targets.extend(jump_target(inst, item.address));
+                if let Jalr { .. } = inst {
+                    targets.extend(*pair);
+                }
+            }
+        }
+        let mut labels: BTreeMap<u64, String> = targets
+            .into_iter()
+            .filter(|address| boundaries.contains(address) && !self.symbols.contains_key(address))
+            .enumerate()
+            .map(|(i, address)| (address, format!(".L{}", i)))
+            .collect();
+        labels.extend(self.symbols.clone());
+        let with_label = |address: u64| match labels.get(&address) {
+            Some(label) => format!("{:x} <{}>", address, label),
+            None => format!("{:x}", address),
+        };
+
+        items.into_iter().zip(pairs).map(|(item, pair)| {
+            let text = match &item.instruction {
+                Ok(inst) => {
+                    let mut tokens = Vec::new();
+                    for token in inst.tokens_with(&self.options.with_pc(item.address).with_compressed(item.compressed())) {
+                        let label = match token {
+                            Token::Address(address) => labels.get(&address),
+                            _ => None,
+                        };
+                        tokens.push(token);
+                        if let Some(label) = label {
+                            tokens.push(Token::Text(format!(" <{}>", label)));
+                        }
+                    }
+                    Rendered::new(tokens, self.options.syntax, &Plain).to_string()
+                }
+                Err(err) if err.kind == DecodeErrorKind::Truncated || item.len < 2 => {
+                    let bytes: Vec<String> = item.bytes.iter().map(|b| format!("0x{:02x}", b)).collect();
+                    format!(".byte\t{}", bytes.join(", "))
+                }
+                Err(_) => format!(".insn\t{}, 0x{}", item.len, hex_bytes(item.bytes)),
+            };
+            ListingLine {
+                address: item.address,
+                bytes: item.bytes,
+                label: labels.get(&item.address).cloned(),
+                instruction: item.instruction,
+                text,
+                comment: pair.map(|address| format!("# {}", with_label(address))),
+            }
+        }).collect()
+    }
+}
+
+impl Display for ListingLine<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>8x}:\t{:<20}\t{}", self.address, hex_bytes(self.bytes), self.text)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " {}", comment)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Listing<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.decoder.xlen() / 4;
+        for line in self.lines() {
+            if let Some(label) = &line.label {
+                writeln!(f, "\n{:0width$x} <{}>:", line.address, label, width = width)?;
+            }
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
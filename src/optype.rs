@@ -117,7 +117,7 @@ impl OpType {
     pub fn new_lui(from: u32) -> Self {
         let i = Self::new_u(from);
         match i {
-            OpType::U { rd, imm, uimm } => {
+            OpType::U { rd, uimm, .. } => {
                 OpType::Lui { rd, imm: uimm }
             }
             _ => unreachable!(),
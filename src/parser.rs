@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::csr::CsrRegister;
+use crate::error::ParseError;
+use crate::instruction::{FenceFlags, Instruction};
+use crate::instruction::Instruction::*;
+use crate::format::{CSR_READ_ALIASES, CSR_SWAP_ALIASES, CSR_SWAP_IMM_ALIASES};
+use crate::register::Register;
+use crate::register::Register::{ReturnAddress, Zero};
+use crate::MAX_OPCODE_SIZE;
+
+fn parse_register(s: &str) -> Result<Register, ParseError> {
+    // `Display` writes both names, e.g. `a0/x10`
+    let mut reg = None;
+    for name in s.split('/') {
+        let parsed = name.parse::<Register>().map_err(|_| ParseError::InvalidRegister(s.to_string()))?;
+        if reg.replace(parsed).is_some_and(|prev| prev != parsed) {
+            return Err(ParseError::InvalidRegister(s.to_string()));
+        }
+    }
+    reg.ok_or_else(|| ParseError::InvalidRegister(s.to_string()))
+}
+
+/// Parses a decimal, `0x` hex or `0b` binary immediate. Hex and binary values
+/// may use all 64 bits, `0xFFFFFFFFFFFFFFFF` is -1.
+pub(crate) fn parse_imm(s: &str) -> Result<i64, ParseError> {
+    let err = || ParseError::InvalidImm(s.to_string());
+    // `Display` writes `dec(0xHEX)`, the decimal part is enough
+    let s = match s.trim().split_once('(') {
+        Some((dec, hex)) if hex.ends_with(')') => dec,
+        _ => s.trim(),
+    };
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let digits = digits.replace('_', "");
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).map_err(|_| err())? as i64
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        u64::from_str_radix(bin, 2).map_err(|_| err())? as i64
+    } else {
+        let value = digits.parse::<u64>().map_err(|_| err())?;
+        if value > i64::MAX as u64 + negative as u64 {
+            return Err(err());
+        }
+        value as i64
+    };
+    Ok(if negative { value.wrapping_neg() } else { value })
+}
+
+fn parse_uimm(s: &str) -> Result<u64, ParseError> {
+    match parse_imm(s)? {
+        imm if imm < 0 => Err(ParseError::InvalidImm(s.to_string())),
+        imm => Ok(imm as u64),
+    }
+}
+
+/// Accepts a CSR name as printed by `Display`, or its 12-bit address.
+fn parse_csr(s: &str) -> Result<CsrRegister, ParseError> {
+    let name = s.trim().to_lowercase();
+    let address = match name.strip_prefix("unknown(").and_then(|n| n.strip_suffix(')')) {
+        Some(num) => Some(num),
+        None if name.starts_with(|c: char| c.is_ascii_digit()) => Some(name.as_str()),
+        None => None,
+    };
+    if let Some(address) = address {
+        return match parse_imm(address) {
+            Ok(num @ 0..=0xFFF) => Ok(CsrRegister::from(num as u64)),
+            _ => Err(ParseError::InvalidCsr(s.to_string())),
+        };
+    }
+    if let Some(num) = name.strip_prefix("invalid(").and_then(|n| n.strip_suffix(')')) {
+        return parse_imm(num).map(|num| CsrRegister::Invalid(num as u64)).map_err(|_| ParseError::InvalidCsr(s.to_string()));
+    }
+    static NAMES: OnceLock<HashMap<String, CsrRegister>> = OnceLock::new();
+    let names = NAMES.get_or_init(|| {
+        (0..0x1000u64)
+            .map(CsrRegister::from)
+            .filter(|csr| !matches!(csr, CsrRegister::Other(_)))
+            .map(|csr| (csr.name(), csr))
+            .collect()
+    });
+    names.get(&name).copied().ok_or_else(|| ParseError::InvalidCsr(s.to_string()))
+}
+
+/// Parses `imm(reg)`, the immediate may be left out.
+fn parse_mem(s: &str) -> Result<(i64, Register), ParseError> {
+    let err = || ParseError::InvalidOperand(s.to_string());
+    let s = s.trim();
+    let open = s.rfind('(').ok_or_else(err)?;
+    let reg = s[open + 1..].strip_suffix(')').ok_or_else(err)?;
+    let imm = s[..open].trim();
+    let imm = if imm.is_empty() { 0 } else { parse_imm(imm)? };
+    Ok((imm, parse_register(reg)?))
+}
+
+/// `rs1` or `offset(rs1)` of the `jr` and `jalr` aliases.
+fn parse_jump_target(s: &str) -> Result<(i64, Register), ParseError> {
+    match s.contains('(') {
+        true => parse_mem(s),
+        false => Ok((0, parse_register(s)?)),
+    }
+}
+
+fn parse_fence_flags(s: &str) -> Result<FenceFlags, ParseError> {
+    let mut bits = 0u8;
+    for c in s.trim().to_lowercase().chars() {
+        bits |= match c {
+            'i' => 0b1000,
+            'o' => 0b0100,
+            'r' => 0b0010,
+            'w' => 0b0001,
+            _ => return Err(ParseError::InvalidOperand(s.to_string())),
+        };
+    }
+    Ok(FenceFlags::from(bits))
+}
+
+/// Parses `.insn len, 0x...` as printed for [`Instruction::Long`].
+fn parse_long(len: &str, hex: &str) -> Result<Instruction, ParseError> {
+    let len = match parse_imm(len)? {
+        len @ 6..=22 if len % 2 == 0 => len as usize,
+        _ => return Err(ParseError::InvalidImm(len.to_string())),
+    };
+    let err = || ParseError::InvalidImm(hex.to_string());
+    let digits = hex.trim().strip_prefix("0x").ok_or_else(err)?.replace('_', "");
+    if digits.len() > len * 2 || !digits.is_ascii() {
+        return Err(err());
+    }
+    let digits = format!("{:0>w$}", digits, w = len * 2);
+    let mut raw = [0u8; MAX_OPCODE_SIZE];
+    for (i, byte) in raw[..len].iter_mut().rev().enumerate() {
+        *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).map_err(|_| err())?;
+    }
+    Ok(Long { len, raw })
+}
+
+fn operands<const N: usize>(ops: &[&str]) -> Result<[String; N], ParseError> {
+    if ops.len() != N {
+        return Err(ParseError::OperandCount { expected: N, found: ops.len() });
+    }
+    Ok(std::array::from_fn(|i| ops[i].to_string()))
+}
+
+fn r_type(ops: &[&str], f: fn(Register, Register, Register) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rd, rs1, rs2] = operands(ops)?;
+    Ok(f(parse_register(&rd)?, parse_register(&rs1)?, parse_register(&rs2)?))
+}
+
+fn i_type(ops: &[&str], f: fn(Register, Register, i64) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rd, rs1, imm] = operands(ops)?;
+    Ok(f(parse_register(&rd)?, parse_register(&rs1)?, parse_imm(&imm)?))
+}
+
+fn shift(ops: &[&str], f: fn(Register, Register, u64) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rd, rs1, shamt] = operands(ops)?;
+    Ok(f(parse_register(&rd)?, parse_register(&rs1)?, parse_uimm(&shamt)?))
+}
+
+/// Loads and stores, `reg, imm(rs1)`.
+fn mem(ops: &[&str], f: fn(Register, Register, i64) -> Instruction) -> Result<Instruction, ParseError> {
+    let [reg, addr] = operands(ops)?;
+    let (imm, rs1) = parse_mem(&addr)?;
+    Ok(f(parse_register(&reg)?, rs1, imm))
+}
+
+fn branch(ops: &[&str], f: fn(Register, Register, i64) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rs1, rs2, imm] = operands(ops)?;
+    Ok(f(parse_register(&rs1)?, parse_register(&rs2)?, parse_imm(&imm)?))
+}
+
+/// `rd, rs2, (rs1)`, ordering bits come from the mnemonic or the `(aq:.. rq:..)` suffix.
+fn amo(ops: &[&str], aq: bool, rl: bool, f: fn(Register, Register, Register, bool, bool) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rd, rs2, addr] = operands(ops)?;
+    let (imm, rs1) = parse_mem(&addr)?;
+    if imm != 0 {
+        return Err(ParseError::InvalidOperand(addr));
+    }
+    Ok(f(parse_register(&rd)?, rs1, parse_register(&rs2)?, aq, rl))
+}
+
+fn csr(ops: &[&str], f: fn(Register, Register, CsrRegister) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rd, csr, rs1] = operands(ops)?;
+    Ok(f(parse_register(&rd)?, parse_register(&rs1)?, parse_csr(&csr)?))
+}
+
+fn csr_imm(ops: &[&str], f: fn(Register, CsrRegister, i64) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rd, csr, imm] = operands(ops)?;
+    Ok(f(parse_register(&rd)?, parse_csr(&csr)?, parse_imm(&imm)?))
+}
+
+fn two_regs(ops: &[&str], f: fn(Register, Register) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rd, rs] = operands(ops)?;
+    Ok(f(parse_register(&rd)?, parse_register(&rs)?))
+}
+
+/// Branches against zero, `beqz rs, imm`.
+fn branch_zero(ops: &[&str], f: fn(Register, i64) -> Instruction) -> Result<Instruction, ParseError> {
+    let [rs, imm] = operands(ops)?;
+    Ok(f(parse_register(&rs)?, parse_imm(&imm)?))
+}
+
+/// CSR writes with the destination left out, `csrw csr, rs`.
+fn csr_write(ops: &[&str], f: fn(CsrRegister, Register) -> Instruction) -> Result<Instruction, ParseError> {
+    let [csr, rs] = operands(ops)?;
+    Ok(f(parse_csr(&csr)?, parse_register(&rs)?))
+}
+
+fn csr_write_imm(ops: &[&str], f: fn(CsrRegister, i64) -> Instruction) -> Result<Instruction, ParseError> {
+    let [csr, imm] = operands(ops)?;
+    Ok(f(parse_csr(&csr)?, parse_imm(&imm)?))
+}
+
+/// Aliases with the CSR in the name, like `rdcycle rd` or `fsflags rd, rs`.
+/// The destination of the swapping ones can be left out.
+fn parse_named_csr(name: &str, ops: &[&str]) -> Result<Option<Instruction>, ParseError> {
+    let lookup = |table: &[(&str, CsrRegister)]| table.iter().find(|(alias, _)| *alias == name).map(|(_, csr)| *csr);
+    let optional_rd = || match *ops {
+        [value] => Ok(("zero", value)),
+        _ => operands::<2>(ops).map(|_| (ops[0], ops[1])),
+    };
+    if let Some(csr) = lookup(&CSR_READ_ALIASES) {
+        let [rd] = operands(ops)?;
+        return Ok(Some(Csrrs { rd: parse_register(&rd)?, rs1: Register::Zero, csr }));
+    }
+    if let Some(csr) = lookup(&CSR_SWAP_ALIASES) {
+        let (rd, rs1) = optional_rd()?;
+        return Ok(Some(Csrrw { rd: parse_register(rd)?, rs1: parse_register(rs1)?, csr }));
+    }
+    if let Some(csr) = lookup(&CSR_SWAP_IMM_ALIASES) {
+        let (rd, imm) = optional_rd()?;
+        return Ok(Some(Csrrwi { rd: parse_register(rd)?, csr, imm: parse_imm(imm)? }));
+    }
+    Ok(None)
+}
+
+/// The `aq` and `rl` bits of an AMO.
+type Ordering = (bool, bool);
+
+/// Splits off the `(aq:true rq:false)` suffix `Display` writes after AMOs.
+fn ordering_suffix(operands: &str) -> Result<(&str, Option<Ordering>), ParseError> {
+    let Some(start) = operands.find("(aq:") else {
+        return Ok((operands, None));
+    };
+    let err = || ParseError::InvalidOperand(operands[start..].to_string());
+    let inner = operands[start + 1..].trim_end().strip_suffix(')').ok_or_else(err)?;
+    let mut aq = None;
+    let mut rl = None;
+    for part in inner.split_whitespace() {
+        match part.split_once(':') {
+            Some(("aq", value)) => aq = value.parse().ok(),
+            Some(("rq" | "rl", value)) => rl = value.parse().ok(),
+            _ => return Err(err()),
+        }
+    }
+    Ok((operands[..start].trim_end(), Some((aq.ok_or_else(err)?, rl.ok_or_else(err)?))))
+}
+
+impl FromStr for CsrRegister {
+    type Err = ParseError;
+
+    /// Accepts what [`CsrRegister::name`] returns, or a 12-bit address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_csr(s)
+    }
+}
+
+impl FromStr for FenceFlags {
+    type Err = ParseError;
+
+    /// Accepts a set like `iorw` in any order, or an empty string for no flags.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_fence_flags(s)
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = ParseError;
+
+    /// Parses one instruction in the syntax `Display` prints. Registers can be ABI or `xN` names, immediates decimal or hex,
+    /// and CSRs are given by name or address. Pseudo-instructions that stand for
+    /// a single instruction, like `mv` or `csrr`, are accepted too. The result is
+    /// checked to be encodable.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let line = s.trim();
+        let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let (rest, ordering) = ordering_suffix(rest.trim())?;
+        let ops: Vec<&str> = if rest.is_empty() { Vec::new() } else { rest.split(',').map(str::trim).collect() };
+        let mnemonic = mnemonic.to_lowercase();
+
+        let (base, suffix_aq, suffix_rl) = match mnemonic.strip_suffix(".aqrl") {
+            Some(base) => (base, true, true),
+            None => match (mnemonic.strip_suffix(".aq"), mnemonic.strip_suffix(".rl")) {
+                (Some(base), _) => (base, true, false),
+                (_, Some(base)) => (base, false, true),
+                _ => (mnemonic.as_str(), false, false),
+            },
+        };
+        let (aq, rl) = ordering.unwrap_or((suffix_aq, suffix_rl));
+        let no_operands = |inst: Instruction| match ops.len() {
+            0 => Ok(inst),
+            found => Err(ParseError::OperandCount { expected: 0, found }),
+        };
+
+        if let Some(inst) = parse_named_csr(&mnemonic, &ops)? {
+            inst.encode()?;
+            return Ok(inst);
+        }
+        let inst = match base {
+            "amoswap.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amoswapw { rd, rs1, rs2, aq, rl })?,
+            "amoadd.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amoaddw { rd, rs1, rs2, aq, rl })?,
+            "amoxor.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amoxorw { rd, rs1, rs2, aq, rl })?,
+            "amoand.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amoandw { rd, rs1, rs2, aq, rl })?,
+            "amoor.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amoorw { rd, rs1, rs2, aq, rl })?,
+            "amomin.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amominw { rd, rs1, rs2, aq, rl })?,
+            "amomax.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amomaxw { rd, rs1, rs2, aq, rl })?,
+            "amominu.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amominuw { rd, rs1, rs2, aq, rl })?,
+            "amomaxu.w" => amo(&ops, aq, rl, |rd, rs1, rs2, aq, rl| Amomaxuw { rd, rs1, rs2, aq, rl })?,
+            _ if base != mnemonic || ordering.is_some() => return Err(ParseError::UnknownMnemonic(mnemonic)),
+
+            "add" => r_type(&ops, |rd, rs1, rs2| Add { rd, rs1, rs2 })?,
+            "sub" => r_type(&ops, |rd, rs1, rs2| Sub { rd, rs1, rs2 })?,
+            "sll" => r_type(&ops, |rd, rs1, rs2| Sll { rd, rs1, rs2 })?,
+            "slt" => r_type(&ops, |rd, rs1, rs2| Slt { rd, rs1, rs2 })?,
+            "sltu" => r_type(&ops, |rd, rs1, rs2| Sltu { rd, rs1, rs2 })?,
+            "xor" => r_type(&ops, |rd, rs1, rs2| Xor { rd, rs1, rs2 })?,
+            "srl" => r_type(&ops, |rd, rs1, rs2| Srl { rd, rs1, rs2 })?,
+            "sra" => r_type(&ops, |rd, rs1, rs2| Sra { rd, rs1, rs2 })?,
+            "or" => r_type(&ops, |rd, rs1, rs2| Or { rd, rs1, rs2 })?,
+            "and" => r_type(&ops, |rd, rs1, rs2| And { rd, rs1, rs2 })?,
+            "addw" => r_type(&ops, |rd, rs1, rs2| Addw { rd, rs1, rs2 })?,
+            "subw" => r_type(&ops, |rd, rs1, rs2| Subw { rd, rs1, rs2 })?,
+            "sllw" => r_type(&ops, |rd, rs1, rs2| Sllw { rd, rs1, rs2 })?,
+            "srlw" => r_type(&ops, |rd, rs1, rs2| Srlw { rd, rs1, rs2 })?,
+            "sraw" => r_type(&ops, |rd, rs1, rs2| Sraw { rd, rs1, rs2 })?,
+            "mul" => r_type(&ops, |rd, rs1, rs2| Mul { rd, rs1, rs2 })?,
+            "mulh" => r_type(&ops, |rd, rs1, rs2| Mulh { rd, rs1, rs2 })?,
+            "mulhsu" => r_type(&ops, |rd, rs1, rs2| Mulhsu { rd, rs1, rs2 })?,
+            "mulhu" => r_type(&ops, |rd, rs1, rs2| Mulhu { rd, rs1, rs2 })?,
+            "div" => r_type(&ops, |rd, rs1, rs2| Div { rd, rs1, rs2 })?,
+            "divu" => r_type(&ops, |rd, rs1, rs2| Divu { rd, rs1, rs2 })?,
+            "rem" => r_type(&ops, |rd, rs1, rs2| Rem { rd, rs1, rs2 })?,
+            "remu" => r_type(&ops, |rd, rs1, rs2| Remu { rd, rs1, rs2 })?,
+            "mulw" => r_type(&ops, |rd, rs1, rs2| Mulw { rd, rs1, rs2 })?,
+            "divw" => r_type(&ops, |rd, rs1, rs2| Divw { rd, rs1, rs2 })?,
+            "divuw" => r_type(&ops, |rd, rs1, rs2| Divuw { rd, rs1, rs2 })?,
+            "remw" => r_type(&ops, |rd, rs1, rs2| Remw { rd, rs1, rs2 })?,
+            "remuw" => r_type(&ops, |rd, rs1, rs2| Remuw { rd, rs1, rs2 })?,
+
+            "addi" => i_type(&ops, |rd, rs1, imm| Addi { rd, rs1, imm })?,
+            "slti" => i_type(&ops, |rd, rs1, imm| Slti { rd, rs1, imm })?,
+            "sltiu" => i_type(&ops, |rd, rs1, imm| Sltiu { rd, rs1, imm })?,
+            "xori" => i_type(&ops, |rd, rs1, imm| Xori { rd, rs1, imm })?,
+            "ori" => i_type(&ops, |rd, rs1, imm| Ori { rd, rs1, imm })?,
+            "andi" => i_type(&ops, |rd, rs1, imm| Andi { rd, rs1, imm })?,
+            "addiw" => i_type(&ops, |rd, rs1, imm| Addiw { rd, rs1, imm })?,
+            "slli" => shift(&ops, |rd, rs1, shamt| Slli { rd, rs1, shamt })?,
+            "srli" => shift(&ops, |rd, rs1, shamt| Srli { rd, rs1, shamt })?,
+            "srai" => shift(&ops, |rd, rs1, shamt| Srai { rd, rs1, shamt })?,
+            "slliw" => shift(&ops, |rd, rs1, shamt| Slliw { rd, rs1, shamt })?,
+            "srliw" => shift(&ops, |rd, rs1, shamt| Srliw { rd, rs1, shamt })?,
+            "sraiw" => shift(&ops, |rd, rs1, shamt| Sraiw { rd, rs1, shamt })?,
+
+            "sb" => mem(&ops, |rs2, rs1, imm| Sb { rs1, rs2, imm })?,
+            "sh" => mem(&ops, |rs2, rs1, imm| Sh { rs1, rs2, imm })?,
+            "sw" => mem(&ops, |rs2, rs1, imm| Sw { rs1, rs2, imm })?,
+            "sd" => mem(&ops, |rs2, rs1, imm| Sd { rs1, rs2, imm })?,
+            "lb" => mem(&ops, |rd, rs1, imm| Lb { rd, rs1, imm })?,
+            "lh" => mem(&ops, |rd, rs1, imm| Lh { rd, rs1, imm })?,
+            "lw" => mem(&ops, |rd, rs1, imm| Lw { rd, rs1, imm })?,
+            "lbu" => mem(&ops, |rd, rs1, imm| Lbu { rd, rs1, imm })?,
+            "lhu" => mem(&ops, |rd, rs1, imm| Lhu { rd, rs1, imm })?,
+            "lwu" => mem(&ops, |rd, rs1, imm| Lwu { rd, rs1, imm })?,
+            "ld" => mem(&ops, |rd, rs1, imm| Ld { rd, rs1, imm })?,
+
+            "beq" => branch(&ops, |rs1, rs2, imm| Beq { rs1, rs2, imm })?,
+            "bne" => branch(&ops, |rs1, rs2, imm| Bne { rs1, rs2, imm })?,
+            "blt" => branch(&ops, |rs1, rs2, imm| Blt { rs1, rs2, imm })?,
+            "bltu" => branch(&ops, |rs1, rs2, imm| Bltu { rs1, rs2, imm })?,
+            "bge" => branch(&ops, |rs1, rs2, imm| Bge { rs1, rs2, imm })?,
+            "bgeu" => branch(&ops, |rs1, rs2, imm| Bgeu { rs1, rs2, imm })?,
+
+            "auipc" => {
+                let [rd, imm] = operands(&ops)?;
+                Auipc { rd: parse_register(&rd)?, imm: parse_imm(&imm)? }
+            }
+            "lui" => {
+                let [rd, uimm] = operands(&ops)?;
+                Lui { rd: parse_register(&rd)?, uimm: parse_uimm(&uimm)? }
+            }
+            "jal" if ops.len() == 1 => Jal { rd: ReturnAddress, imm: parse_imm(ops[0])? },
+            "jal" => {
+                let [rd, imm] = operands(&ops)?;
+                Jal { rd: parse_register(&rd)?, imm: parse_imm(&imm)? }
+            }
+            "jalr" if ops.len() == 2 => mem(&ops, |rd, rs1, imm| Jalr { rd, rs1, imm })?,
+            "jalr" if ops.len() == 1 => {
+                let (imm, rs1) = parse_jump_target(ops[0])?;
+                Jalr { rd: ReturnAddress, rs1, imm }
+            }
+            "jalr" => i_type(&ops, |rd, rs1, imm| Jalr { rd, rs1, imm })?,
+            "ret" => no_operands(Jalr { rd: Zero, rs1: ReturnAddress, imm: 0 })?,
+
+            "ecall" => no_operands(Ecall)?,
+            "ebreak" => no_operands(Ebreak)?,
+            "uret" => no_operands(Uret)?,
+            "sret" => no_operands(Sret)?,
+            "wfi" => no_operands(Wfi)?,
+            "mret" => no_operands(Mret)?,
+            "mnret" => no_operands(Mnret)?,
+            "dret" => no_operands(Dret)?,
+            "csrrw" => csr(&ops, |rd, rs1, csr| Csrrw { rd, rs1, csr })?,
+            "csrrs" => csr(&ops, |rd, rs1, csr| Csrrs { rd, rs1, csr })?,
+            "csrrc" => csr(&ops, |rd, rs1, csr| Csrrc { rd, rs1, csr })?,
+            "csrrwi" => csr_imm(&ops, |rd, csr, imm| Csrrwi { rd, csr, imm })?,
+            "csrrsi" => csr_imm(&ops, |rd, csr, imm| Csrrsi { rd, csr, imm })?,
+            "csrrci" => csr_imm(&ops, |rd, csr, imm| Csrrci { rd, csr, imm })?,
+
+            "fence.i" => no_operands(FenceI)?,
+            "fence.tso" => no_operands(FenceTso)?,
+            "fence" if ops.is_empty() => Fence { pred: FenceFlags::from(0b1111), succ: FenceFlags::from(0b1111) },
+            "fence" => {
+                let [pred, succ] = operands(&ops)?;
+                Fence { pred: parse_fence_flags(&pred)?, succ: parse_fence_flags(&succ)? }
+            }
+            ".insn" => {
+                let [len, hex] = operands(&ops)?;
+                return parse_long(&len, &hex);
+            }
+
+            // Pseudo-instructions that map to a single instruction
+            "nop" => no_operands(Addi { rd: Zero, rs1: Zero, imm: 0 })?,
+            "li" => {
+                let [rd, imm] = operands(&ops)?;
+                Addi { rd: parse_register(&rd)?, rs1: Zero, imm: parse_imm(&imm)? }
+            }
+            "mv" => two_regs(&ops, |rd, rs1| Addi { rd, rs1, imm: 0 })?,
+            "sext.w" => two_regs(&ops, |rd, rs1| Addiw { rd, rs1, imm: 0 })?,
+            "not" => two_regs(&ops, |rd, rs1| Xori { rd, rs1, imm: -1 })?,
+            "zext.b" => two_regs(&ops, |rd, rs1| Andi { rd, rs1, imm: 255 })?,
+            "neg" => two_regs(&ops, |rd, rs2| Sub { rd, rs1: Zero, rs2 })?,
+            "negw" => two_regs(&ops, |rd, rs2| Subw { rd, rs1: Zero, rs2 })?,
+            "seqz" => two_regs(&ops, |rd, rs1| Sltiu { rd, rs1, imm: 1 })?,
+            "snez" => two_regs(&ops, |rd, rs2| Sltu { rd, rs1: Zero, rs2 })?,
+            "sltz" => two_regs(&ops, |rd, rs1| Slt { rd, rs1, rs2: Zero })?,
+            "sgtz" => two_regs(&ops, |rd, rs2| Slt { rd, rs1: Zero, rs2 })?,
+
+            "beqz" => branch_zero(&ops, |rs1, imm| Beq { rs1, rs2: Zero, imm })?,
+            "bnez" => branch_zero(&ops, |rs1, imm| Bne { rs1, rs2: Zero, imm })?,
+            "blez" => branch_zero(&ops, |rs2, imm| Bge { rs1: Zero, rs2, imm })?,
+            "bgez" => branch_zero(&ops, |rs1, imm| Bge { rs1, rs2: Zero, imm })?,
+            "bltz" => branch_zero(&ops, |rs1, imm| Blt { rs1, rs2: Zero, imm })?,
+            "bgtz" => branch_zero(&ops, |rs2, imm| Blt { rs1: Zero, rs2, imm })?,
+            "bgt" => branch(&ops, |rs, rt, imm| Blt { rs1: rt, rs2: rs, imm })?,
+            "ble" => branch(&ops, |rs, rt, imm| Bge { rs1: rt, rs2: rs, imm })?,
+            "bgtu" => branch(&ops, |rs, rt, imm| Bltu { rs1: rt, rs2: rs, imm })?,
+            "bleu" => branch(&ops, |rs, rt, imm| Bgeu { rs1: rt, rs2: rs, imm })?,
+            "j" => {
+                let [imm] = operands(&ops)?;
+                Jal { rd: Zero, imm: parse_imm(&imm)? }
+            }
+            "jr" => {
+                let [target] = operands(&ops)?;
+                let (imm, rs1) = parse_jump_target(&target)?;
+                Jalr { rd: Zero, rs1, imm }
+            }
+
+            "csrr" => {
+                let [rd, csr] = operands(&ops)?;
+                Csrrs { rd: parse_register(&rd)?, rs1: Zero, csr: parse_csr(&csr)? }
+            }
+            "csrw" => csr_write(&ops, |csr, rs1| Csrrw { rd: Zero, rs1, csr })?,
+            "csrs" => csr_write(&ops, |csr, rs1| Csrrs { rd: Zero, rs1, csr })?,
+            "csrc" => csr_write(&ops, |csr, rs1| Csrrc { rd: Zero, rs1, csr })?,
+            "csrwi" => csr_write_imm(&ops, |csr, imm| Csrrwi { rd: Zero, csr, imm })?,
+            "csrsi" => csr_write_imm(&ops, |csr, imm| Csrrsi { rd: Zero, csr, imm })?,
+            "csrci" => csr_write_imm(&ops, |csr, imm| Csrrci { rd: Zero, csr, imm })?,
+            "unimp" => no_operands(Csrrw { rd: Zero, rs1: Zero, csr: CsrRegister::Cycle })?,
+            "pause" => no_operands(Fence { pred: FenceFlags::from(0b0001), succ: FenceFlags::from(0) })?,
+            _ => return Err(ParseError::UnknownMnemonic(mnemonic)),
+        };
+        inst.encode()?;
+        Ok(inst)
+    }
+}
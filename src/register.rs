@@ -1,4 +1,6 @@
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::error::ParseError;
 use crate::register::Register::*;
 
 #[repr(usize)]
@@ -38,51 +40,51 @@ pub enum Register {
     Temp6,
 }
 
+const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+const NUMERIC_NAMES: [&str; 32] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7",
+    "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15",
+    "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23",
+    "x24", "x25", "x26", "x27", "x28", "x29", "x30", "x31",
+];
+
 impl Display for Register {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let _ = write!(f, "\x1B[32m");
-        let retval = match self {
-            Zero => write!(f, "zero"),
-            ReturnAddress => write!(f, "ra"),
-            StackPointer => write!(f, "sp"),
-            GlobalPointer => write!(f, "gp"),
-            ThreadPointer => write!(f, "tp"),
-            Temp0 => write!(f, "t0"),
-            Temp1 => write!(f, "t1"),
-            Temp2 => write!(f, "t2"),
-            Saved0 => write!(f, "s0"),
-            Saved1 => write!(f, "s1"),
-            ArgumentRetval0 => write!(f, "a0"),
-            ArgumentRetval1 => write!(f, "a1"),
-            Argument2 => write!(f, "a2"),
-            Argument3 => write!(f, "a3"),
-            Argument4 => write!(f, "a4"),
-            Argument5 => write!(f, "a5"),
-            Argument6 => write!(f, "a6"),
-            Argument7 => write!(f, "a7"),
-            Saved2 => write!(f, "s2"),
-            Saved3 => write!(f, "s3"),
-            Saved4 => write!(f, "s4"),
-            Saved5 => write!(f, "s5"),
-            Saved6 => write!(f, "s6"),
-            Saved7 => write!(f, "s7"),
-            Saved8 => write!(f, "s8"),
-            Saved9 => write!(f, "s9"),
-            Saved10 => write!(f, "s10"),
-            Saved11 => write!(f, "s11"),
-            Temp3 => write!(f, "t3"),
-            Temp4 => write!(f, "t4"),
-            Temp5 => write!(f, "t5"),
-            Temp6 => write!(f, "t6"),
+        write!(f, "{}/{}", self.abi_name(), self.numeric_name())
+    }
+}
+
+impl FromStr for Register {
+    type Err = ParseError;
+
+    /// Accepts `x0` - `x31`, the ABI names and `fp` for `s0`, in any case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        let num = match name.as_str() {
+            "fp" => Some(8),
+            _ => NUMERIC_NAMES.iter().chain(ABI_NAMES.iter()).position(|n| *n == name).map(|i| i % 32),
         };
-        let _ = write!(f, "\x1B[0m/\x1B[32m");
-        let _ = write!(f, "x{}", *self as usize);
-        let _ = write!(f, "\x1B[0m");
-        retval
+        num.map(Register::from).ok_or_else(|| ParseError::InvalidRegister(s.to_string()))
     }
 }
 
 impl Register {
+    /// The ABI name, e.g. `a0`. `s0` is never printed as `fp`.
+    pub fn abi_name(&self) -> &'static str {
+        ABI_NAMES[*self as usize]
+    }
+
+    /// The architectural name, e.g. `x10`.
+    pub fn numeric_name(&self) -> &'static str {
+        NUMERIC_NAMES[*self as usize]
+    }
+
     pub fn from_rvc(num: u8) -> Self {
         if num <= 0b111 {
             Self::from(num as usize + 8)
@@ -90,6 +92,14 @@ impl Register {
             Zero
         }
     }
+
+    /// Returns the 3-bit RVC register number, only x8 - x15 have one.
+    pub fn to_rvc(&self) -> Option<u8> {
+        match *self as usize {
+            num @ 8..=15 => Some(num as u8 - 8),
+            _ => None,
+        }
+    }
 }
 
 impl From<Register> for usize {
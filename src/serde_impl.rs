@@ -0,0 +1,30 @@
+//! Registers, CSRs and fence sets are stored as the names the disassembler
+//! prints (`"a0"`, `"mstatus"`, `"rw"`), so traces stay readable. Instructions
+//! derive their impls and come out as e.g. `{"Addi":{"rd":"a0","rs1":"zero","imm":5}}`.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::csr::CsrRegister;
+use crate::instruction::FenceFlags;
+use crate::register::Register;
+
+/// Serializes with `$name` and deserializes through `FromStr`.
+macro_rules! impl_as_string {
+    ($ty:ty, $name:expr) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&$name(self))
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+            }
+        }
+    };
+}
+
+impl_as_string!(Register, |reg: &Register| reg.abi_name().to_string());
+impl_as_string!(CsrRegister, CsrRegister::name);
+impl_as_string!(FenceFlags, FenceFlags::to_string);
@@ -0,0 +1,74 @@
+use crate::compressed::compressed_mnemonic;
+use crate::decoder::Decoder;
+use crate::error::DecodeError;
+use crate::instruction::Instruction;
+use crate::opcode_size;
+
+/// One instruction, or undecodable bytes, from an [`InstructionStream`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamItem<'a> {
+    pub address: u64,
+    /// Number of bytes this item covers, same as `bytes.len()`.
+    pub len: usize,
+    pub bytes: &'a [u8],
+    pub instruction: Result<Instruction, DecodeError>,
+}
+
+impl StreamItem<'_> {
+    /// The RVC mnemonic, e.g. `c.addi`, if this is a decoded compressed instruction.
+    pub fn compressed(&self) -> Option<&'static str> {
+        match &self.instruction {
+            Ok(inst) if self.len == 2 => Some(compressed_mnemonic(u16::from_le_bytes([self.bytes[0], self.bytes[1]]), inst)),
+            _ => None,
+        }
+    }
+}
+
+/// Walks little-endian code with mixed instruction lengths. Undecodable
+/// instructions are skipped using their encoded length, so the stream stays in
+/// sync with the instruction boundaries. A trailing partial instruction is
+/// returned as a [`crate::DecodeErrorKind::Truncated`] error.
+#[derive(Debug, Clone)]
+pub struct InstructionStream<'a> {
+    bytes: &'a [u8],
+    address: u64,
+    offset: usize,
+    decoder: Decoder,
+}
+
+impl<'a> InstructionStream<'a> {
+    /// Decodes `bytes` as RV64 with every extension enabled, starting at `base`.
+    pub fn new(bytes: &'a [u8], base: u64) -> Self {
+        Self::with_decoder(bytes, base, Decoder::new())
+    }
+
+    pub fn with_decoder(bytes: &'a [u8], base: u64, decoder: Decoder) -> Self {
+        Self { bytes, address: base, offset: 0, decoder }
+    }
+
+    /// Address of the next item.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+}
+
+impl<'a> Iterator for InstructionStream<'a> {
+    type Item = StreamItem<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.bytes[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        let instruction = self.decoder.decode_bytes(rest);
+        let len = if rest.len() < 2 {
+            rest.len()
+        } else {
+            opcode_size(u16::from_le_bytes([rest[0], rest[1]]) as u32).min(rest.len())
+        };
+        let item = StreamItem { address: self.address, len, bytes: &rest[..len], instruction };
+        self.offset += len;
+        self.address = self.address.wrapping_add(len as u64);
+        Some(item)
+    }
+}